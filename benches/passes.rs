@@ -0,0 +1,54 @@
+#[macro_use]
+extern crate criterion;
+extern crate nono;
+
+use std::hint::black_box;
+
+use criterion::Criterion;
+use nono::parser;
+use nono::puzzle::Grid;
+use nono::puzzle::LinePass;
+use nono::puzzle::Puzzle;
+use nono::solver::Pass;
+
+/// Lines of different shapes, as a name, a clue and the cells of the line.
+const LINES: &[(&str, &[usize], &str)] = &[
+    ("empty", &[3, 1, 4], "...................."),
+    ("crowded", &[5, 6, 7], "...................."),
+    (
+        "scattered",
+        &[1, 1, 1, 1, 1, 1, 1, 1],
+        "..............................",
+    ),
+    ("partial", &[2, 4, 1, 3], "..#...x..##......#.x....."),
+    ("nearly-done", &[2, 4, 1, 3], "xx##x####x#x.###xxxxxxxx."),
+    (
+        "long",
+        &[2, 4, 4, 1, 2, 6, 2],
+        "......................................................",
+    ),
+];
+
+/// A grid of a single line, read as the grid of a puzzle one row high.
+fn line_grid(cells: &str) -> Grid {
+    let s = format!("[{}||{}]", ";".repeat(cells.len() - 1), cells);
+    let ast: parser::Puzzle = s.parse().unwrap();
+    Puzzle::try_from_ast(ast).unwrap().grid().clone()
+}
+
+fn passes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("passes");
+    for pass in Pass::all() {
+        for (name, clue, cells) in LINES {
+            let mut grid = line_grid(cells);
+            let line = grid.horz_mut(0);
+            group.bench_function(format!("{}/{}", pass.name(), name), |b| {
+                b.iter(|| pass.run(black_box(clue), &line))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, passes);
+criterion_main!(benches);
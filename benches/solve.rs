@@ -0,0 +1,38 @@
+#[macro_use]
+extern crate criterion;
+extern crate nono;
+
+use criterion::BatchSize;
+use criterion::Criterion;
+use nono::parser;
+use nono::puzzle::Puzzle;
+use nono::solver;
+use nono::solver::Pass;
+use nono::solver::Strategy;
+
+const EXAMPLES: &str = include_str!("../examples.txt");
+
+fn solve(c: &mut Criterion) {
+    let passes = Pass::all();
+    let mut group = c.benchmark_group("solve");
+    group.sample_size(10);
+    for (i, s) in EXAMPLES.lines().enumerate() {
+        group.bench_function(format!("example-{:02}", i + 1), |b| {
+            b.iter_batched(
+                || {
+                    let ast: parser::Puzzle = s.parse().unwrap();
+                    Puzzle::try_from_ast(ast).unwrap()
+                },
+                |mut puzzle| {
+                    let schedule = Strategy::Restart.schedule(&passes, &puzzle);
+                    solver::run(&mut puzzle, &passes, schedule, None, 1)
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, solve);
+criterion_main!(benches);
@@ -0,0 +1,124 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate nono;
+
+use nono::parser::DEFAULT_COLOR;
+use nono::puzzle::Grid;
+use nono::puzzle::LineMut;
+use nono::puzzle::LinePass;
+use nono::solver::Pass;
+
+/// A line to run the passes on: its clue, the palette index of each number
+/// for colored lines, and its cells.
+struct Input {
+    numbers: Vec<usize>,
+    colors: Vec<usize>,
+    grid: Grid,
+}
+
+/// Reads a number of a clue, optionally followed by its color, for example
+/// `3` or `2r`.
+fn number(s: &str) -> Option<(usize, char)> {
+    match s.char_indices().last()? {
+        (i, color) if color.is_alphabetic() && color != 'x' && color != 'X' => {
+            Some((s[..i].parse().ok()?, color))
+        }
+        _ => Some((s.parse().ok()?, DEFAULT_COLOR)),
+    }
+}
+
+/// Reads a clue and a line state written as `<clue>|<cells>`, for example
+/// `3,1|..#x...` or `2r,1b|_.r.b.`.
+///
+/// Cells are written as in the one-line format. A line is colored if any of
+/// its numbers has a color, and then its colored cells must use the colors
+/// of the clue. Puzzles reject clues that do not fit in their lines or that
+/// hold a zero, so the passes are never run on those and neither are they
+/// here.
+fn line(s: &str) -> Option<Input> {
+    let mut parts = s.splitn(2, '|');
+    let clue = parts.next()?;
+    let cells = parts.next()?;
+    let clue = if clue.is_empty() {
+        vec![]
+    } else {
+        clue.split(',').map(number).collect::<Option<Vec<_>>>()?
+    };
+    let (numbers, clue_colors): (Vec<usize>, Vec<char>) = clue.into_iter().unzip();
+    let len = cells.chars().count();
+    // only neighbouring numbers of the same color need a gap
+    let gaps = clue_colors
+        .windows(2)
+        .filter(|pair| pair[0] == pair[1])
+        .count();
+    let needed = numbers
+        .iter()
+        .try_fold(gaps, |sum, n| sum.checked_add(*n))?;
+    if len == 0 || needed > len || numbers.contains(&0) {
+        return None;
+    }
+    let mut palette: Vec<char> = vec![];
+    if clue_colors.iter().any(|color| *color != DEFAULT_COLOR) {
+        for color in &clue_colors {
+            if !palette.contains(color) {
+                palette.push(*color);
+            }
+        }
+    }
+    let colors = if palette.is_empty() {
+        vec![]
+    } else {
+        clue_colors
+            .iter()
+            .map(|color| palette.iter().position(|c| c == color).unwrap())
+            .collect()
+    };
+    let mut grid = Grid::colored(len, 1, palette.clone());
+    for (i, c) in cells.chars().enumerate() {
+        match c {
+            '_' => grid.mask(i, 0),
+            _ => {
+                let mut line = grid.horz_mut(0);
+                match c {
+                    'x' | 'X' => {
+                        line.cross(i);
+                    }
+                    '!' => {
+                        line.fill(i);
+                        line.cross(i);
+                    }
+                    '.' => {}
+                    '#' if palette.is_empty() => {
+                        line.fill(i);
+                    }
+                    _ => {
+                        let index = palette.iter().position(|color| *color == c)?;
+                        line.fill(i);
+                        for other in (0..palette.len()).filter(|other| *other != index) {
+                            line.exclude(i, other);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Some(Input {
+        numbers,
+        colors,
+        grid,
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(mut input) = std::str::from_utf8(data).ok().and_then(line) {
+        for pass in Pass::all() {
+            let line = input.grid.horz_mut(0);
+            if input.colors.is_empty() {
+                pass.run(&input.numbers, &line);
+            } else {
+                pass.run_colored(&input.numbers, &input.colors, &line);
+            }
+        }
+    }
+});
@@ -0,0 +1,18 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate nono;
+
+use nono::parser;
+use nono::puzzle::Puzzle;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        if let Ok(ast) = s.parse::<parser::Puzzle>() {
+            if let Ok(puzzle) = Puzzle::try_from_ast(ast) {
+                let s = format!("{}", puzzle.as_ast());
+                assert!(s.parse::<parser::Puzzle>().is_ok(), "{}", s);
+            }
+        }
+    }
+});
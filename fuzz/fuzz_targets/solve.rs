@@ -0,0 +1,27 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate nono;
+
+use nono::parser;
+use nono::puzzle::Puzzle;
+use nono::solver;
+use nono::solver::Pass;
+use nono::solver::Strategy;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        if let Ok(ast) = s.parse::<parser::Puzzle>() {
+            if let Ok(puzzle) = Puzzle::try_from_ast(ast) {
+                let passes = Pass::all();
+                for strategy in &[Strategy::Restart, Strategy::RoundRobin, Strategy::Dirty] {
+                    let mut puzzle = puzzle.clone();
+                    let schedule = strategy.schedule(&passes, &puzzle);
+                    solver::run(&mut puzzle, &passes, schedule, None, 1);
+                    let s = format!("{}", puzzle.as_ast());
+                    assert!(s.parse::<parser::Puzzle>().is_ok(), "{}", s);
+                }
+            }
+        }
+    }
+});
@@ -0,0 +1,199 @@
+use fixedbitset::FixedBitSet;
+
+use puzzle::Axis;
+use puzzle::Line;
+
+/// Numbers of a clue that are known to be fully placed in a line.
+///
+/// A number is placed once there is a complete block that can only be that
+/// number: the blocks counted from either end of the line up to the first
+/// undecided cell, or every block once the filled cells add up to the clue.
+/// A block counted from the start is not counted again from the end.
+/// In colored lines, `colors` holds the palette index of each number, and
+/// blocks are told apart by color as well as by crossed cells.
+pub fn satisfied(clue: &[usize], colors: &[usize], line: &dyn Line) -> FixedBitSet {
+    let blocks: Vec<(usize, usize)> = clue
+        .iter()
+        .enumerate()
+        .map(|(k, number)| (*number, colors.get(k).cloned().unwrap_or(0)))
+        .collect();
+    let mut satisfied = FixedBitSet::with_capacity(clue.len());
+    if runs(line).as_ref() == Some(&blocks) {
+        satisfied.insert_range(..);
+        return satisfied;
+    }
+    let (from_start, end) = placed(blocks.iter(), 0..line.len(), line);
+    let rest = end.map_or(0, |end| end + 1);
+    let (from_end, _) = placed(
+        blocks[from_start..].iter().rev(),
+        (rest..line.len()).rev(),
+        line,
+    );
+    satisfied.insert_range(..from_start);
+    satisfied.insert_range(clue.len() - from_end..);
+    satisfied
+}
+
+/// The palette index of a cell that is filled with a known color, 0 in
+/// monochrome lines.
+fn color(line: &dyn Line, i: usize) -> Option<usize> {
+    if !line.is_filled(i) {
+        return None;
+    }
+    if line.colors() == 0 {
+        return Some(0);
+    }
+    let mut colors = (0..line.colors()).filter(|color| !line.is_excluded(i, *color));
+    match (colors.next(), colors.next()) {
+        (Some(color), None) => Some(color),
+        _ => None,
+    }
+}
+
+/// The blocks of filled cells with their colors, if every filled cell has a
+/// known color.
+fn runs(line: &dyn Line) -> Option<Vec<(usize, usize)>> {
+    let mut runs: Vec<(usize, usize)> = vec![];
+    let mut previous = None;
+    for i in 0..line.len() {
+        if !line.is_filled(i) {
+            previous = None;
+            continue;
+        }
+        let color = color(line, i)?;
+        match runs.last_mut() {
+            Some(run) if previous == Some(color) => run.0 += 1,
+            _ => runs.push((1, color)),
+        }
+        previous = Some(color);
+    }
+    Some(runs)
+}
+
+/// Number of blocks that are complete and match the numbers in order, going
+/// through the cells in order up to the first undecided one, along with the
+/// last cell of the last of them.
+fn placed<'a, B, C>(blocks: B, cells: C, line: &dyn Line) -> (usize, Option<usize>)
+where
+    B: Iterator<Item = &'a (usize, usize)>,
+    C: Iterator<Item = usize>,
+{
+    let mut blocks = blocks.peekable();
+    let mut placed = 0;
+    let mut end = None;
+    // the length and color of the block being walked through, and its last
+    // cell so far
+    let mut run = (0, 0);
+    let mut last = 0;
+    for i in cells {
+        let next = if line.is_crossed(i) {
+            None
+        } else if line.is_filled(i) {
+            match color(line, i) {
+                Some(color) => Some(color),
+                None => return (placed, end),
+            }
+        } else {
+            return (placed, end);
+        };
+        if run.0 > 0 && next != Some(run.1) {
+            if blocks.next() != Some(&run) {
+                return (placed, end);
+            }
+            placed += 1;
+            end = Some(last);
+            run.0 = 0;
+        }
+        if let Some(color) = next {
+            run = (run.0 + 1, color);
+            last = i;
+        }
+    }
+    if run.0 > 0 && blocks.peek() == Some(&&run) {
+        placed += 1;
+        end = Some(last);
+    }
+    (placed, end)
+}
+
+/// The placed numbers of the clue of every line of a puzzle.
+#[derive(Clone, Debug)]
+pub struct ClueAccounting {
+    satisfied: [Vec<FixedBitSet>; 2],
+    finished: [FixedBitSet; 2],
+}
+
+fn axis_index(axis: Axis) -> usize {
+    match axis {
+        Axis::Horz => 0,
+        Axis::Vert => 1,
+    }
+}
+
+impl ClueAccounting {
+    pub fn new(width: usize, height: usize) -> Self {
+        ClueAccounting {
+            satisfied: [
+                vec![FixedBitSet::with_capacity(0); height],
+                vec![FixedBitSet::with_capacity(0); width],
+            ],
+            finished: [
+                FixedBitSet::with_capacity(height),
+                FixedBitSet::with_capacity(width),
+            ],
+        }
+    }
+
+    /// Recounts the placed numbers of a line.
+    pub fn update(
+        &mut self,
+        axis: Axis,
+        index: usize,
+        clue: &[usize],
+        colors: &[usize],
+        line: &dyn Line,
+    ) {
+        let a = axis_index(axis);
+        let satisfied = satisfied(clue, colors, line);
+        let finished = satisfied.count_ones(..) == clue.len()
+            && (0..line.len()).all(|i| line.is_crossed(i) || line.is_filled(i));
+        self.finished[a].set(index, finished);
+        self.satisfied[a][index] = satisfied;
+    }
+
+    pub fn satisfied(&self, axis: Axis, index: usize) -> &FixedBitSet {
+        &self.satisfied[axis_index(axis)][index]
+    }
+
+    /// Whether every number of the line is placed and no cell of it is left
+    /// undecided, so that no pass has anything left to find on it.
+    pub fn is_finished(&self, axis: Axis, index: usize) -> bool {
+        self.finished[axis_index(axis)].contains(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use oracle::line_grid;
+
+    fn satisfied_of(clue: &[usize], cells: &str) -> Vec<usize> {
+        let mut grid = line_grid(cells);
+        let line = grid.horz_mut(0);
+        satisfied(clue, &[], &line).ones().collect()
+    }
+
+    #[test]
+    fn placed_numbers() {
+        assert_eq!(satisfied_of(&[2, 1, 3], "##x......"), vec![0]);
+        assert_eq!(satisfied_of(&[2, 1, 3], "x##x..x###"), vec![0, 2]);
+        assert_eq!(satisfied_of(&[2, 1, 3], "..#..x###"), vec![2]);
+        assert_eq!(satisfied_of(&[2, 1, 3], ".##.#.###."), vec![0, 1, 2]);
+        assert_eq!(satisfied_of(&[2, 1, 3], "###x......"), Vec::<usize>::new());
+        assert_eq!(satisfied_of(&[], "...."), Vec::<usize>::new());
+        // one block cannot place two numbers
+        assert_eq!(satisfied_of(&[1, 1], "x#x"), vec![0]);
+        assert_eq!(satisfied_of(&[1, 1], "xxxxxxx#"), vec![0]);
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+use std::time::Duration;
+use std::time::Instant;
+
+use cache::LineCache;
+use parser;
+use puzzle::Puzzle;
+use solver::Pass;
+use solver::Steps;
+use solver::Strategy;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PassTimes {
+    /// Number of times the pass was run on a set of lines.
+    pub tasks: usize,
+    /// Number of lines the pass was run on.
+    pub lines: usize,
+    pub time: Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PuzzleTimes {
+    pub passes: usize,
+    pub best: Duration,
+    pub total: Duration,
+}
+
+/// Solves puzzles repeatedly and keeps track of how long each pass takes.
+pub struct Bench {
+    rounds: usize,
+    puzzles: Vec<PuzzleTimes>,
+    passes: Vec<(&'static str, PassTimes)>,
+}
+
+impl Bench {
+    pub fn new(rounds: usize) -> Self {
+        Bench {
+            rounds: rounds.max(1),
+            puzzles: vec![],
+            passes: vec![],
+        }
+    }
+
+    /// Solves the puzzle once per round, timing every task.
+    pub fn run(
+        &mut self,
+        s: &str,
+        passes: &[Pass],
+        strategy: Strategy,
+        cache: Option<&LineCache>,
+        jobs: usize,
+    ) -> Result<(), String> {
+        let mut times = PuzzleTimes {
+            passes: 0,
+            best: Duration::MAX,
+            total: Duration::default(),
+        };
+        for _ in 0..self.rounds {
+            let ast: parser::Puzzle = s.parse()?;
+            let mut puzzle = Puzzle::try_from_ast(ast)?;
+            let schedule = strategy.schedule(passes, &puzzle);
+            let mut steps = Steps::new(&mut puzzle, passes, schedule, cache, jobs);
+            let start = Instant::now();
+            for step in steps.by_ref() {
+                self.record(step.pass.name(), step.lines.ones().count(), step.time);
+            }
+            let elapsed = start.elapsed();
+            times.passes = steps.rounds();
+            times.best = times.best.min(elapsed);
+            times.total += elapsed;
+        }
+        self.puzzles.push(times);
+        Ok(())
+    }
+
+    /// Timings per puzzle, in the order they were run.
+    pub fn puzzles(&self) -> &[PuzzleTimes] {
+        &self.puzzles
+    }
+
+    /// Timings per pass name, in the order passes were first run.
+    pub fn passes(&self) -> &[(&'static str, PassTimes)] {
+        &self.passes
+    }
+
+    fn record(&mut self, pass: &'static str, lines: usize, time: Duration) {
+        let index = match self.passes.iter().position(|(name, _)| *name == pass) {
+            Some(index) => index,
+            None => {
+                self.passes.push((pass, PassTimes::default()));
+                self.passes.len() - 1
+            }
+        };
+        let times = &mut self.passes[index].1;
+        times.tasks += 1;
+        times.lines += lines;
+        times.time += time;
+    }
+}
+
+fn millis(time: Duration) -> f64 {
+    time.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for Bench {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rounds = self.rounds as u32;
+        writeln!(
+            f,
+            "{: <10} {: >8} {: >12} {: >12}",
+            "puzzle", "passes", "best", "mean"
+        )?;
+        for (i, times) in self.puzzles.iter().enumerate() {
+            writeln!(
+                f,
+                "{: <10} {: >8} {: >10.3}ms {: >10.3}ms",
+                i + 1,
+                times.passes,
+                millis(times.best),
+                millis(times.total / rounds)
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{: <10} {: >8} {: >8} {: >12} {: >12}",
+            "pass", "tasks", "lines", "time", "per line"
+        )?;
+        for (name, times) in &self.passes {
+            writeln!(
+                f,
+                "{: <10} {: >8} {: >8} {: >10.3}ms {: >10.3}us",
+                name,
+                times.tasks / self.rounds,
+                times.lines / self.rounds,
+                millis(times.time / rounds),
+                1000.0 * millis(times.time) / times.lines.max(1) as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts() {
+        let passes = Pass::all();
+        let mut bench = Bench::new(3);
+        bench
+            .run("[2;2|2;2]", &passes, Strategy::Restart, None, 1)
+            .unwrap();
+        bench
+            .run(
+                "[1,1,2;1,2;6;1|3;2;1,1;2;3;1,1]",
+                &passes,
+                Strategy::Restart,
+                None,
+                1,
+            )
+            .unwrap();
+        assert!(bench
+            .run("[2;2|2", &passes, Strategy::Restart, None, 1)
+            .is_err());
+
+        let puzzles = bench.puzzles();
+        assert_eq!(puzzles.len(), 2);
+        assert_eq!(puzzles[0].passes, 1);
+        assert_eq!(puzzles[1].passes, 13);
+        assert!(puzzles.iter().all(|times| times.best <= times.total));
+
+        let tasks: usize = bench.passes().iter().map(|(_, times)| times.tasks).sum();
+        assert_eq!(tasks, 3 * (1 + 13));
+        let (name, times) = bench.passes()[0];
+        assert_eq!(name, "crowded");
+        assert_eq!(times.lines, 3 * (2 + 6 + 4));
+    }
+}
@@ -0,0 +1,146 @@
+use fixedbitset::FixedBitSet;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use puzzle::Line;
+use puzzle::LinePass;
+use solver::Hint;
+use solver::Pass;
+
+#[derive(Eq, Hash, PartialEq)]
+struct Key {
+    pass: &'static str,
+    clue: Vec<usize>,
+    filled: FixedBitSet,
+    crossed: FixedBitSet,
+}
+
+impl Key {
+    fn new(pass: &Pass, clue: &[usize], line: &dyn Line) -> Self {
+        let mut filled = FixedBitSet::with_capacity(line.len());
+        let mut crossed = FixedBitSet::with_capacity(line.len());
+        for i in 0..line.len() {
+            filled.set(i, line.is_filled(i));
+            crossed.set(i, line.is_crossed(i));
+        }
+        Key {
+            pass: pass.name(),
+            clue: clue.to_vec(),
+            filled,
+            crossed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// Remembers the hints each pass produced for each combination of clue and
+/// line contents.
+///
+/// Passes only look at the clue and the line, so the same hints come out
+/// every time the same combination is seen again, within a puzzle or across
+/// puzzles. The cache can be shared between threads.
+pub struct LineCache {
+    entries: Mutex<HashMap<Key, Vec<Hint>>>,
+    stats: Mutex<Vec<(&'static str, CacheStats)>>,
+}
+
+impl LineCache {
+    pub fn new() -> Self {
+        LineCache {
+            entries: Mutex::new(HashMap::new()),
+            stats: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn wrap<'a>(&'a self, pass: &'a Pass) -> Cached<'a> {
+        Cached { pass, cache: self }
+    }
+
+    /// Hit and miss counts per pass name, in the order passes were first seen.
+    pub fn stats(&self) -> Vec<(&'static str, CacheStats)> {
+        self.stats.lock().unwrap().clone()
+    }
+
+    fn record(&self, pass: &'static str, hit: bool) {
+        let mut stats = self.stats.lock().unwrap();
+        let index = match stats.iter().position(|(name, _)| *name == pass) {
+            Some(index) => index,
+            None => {
+                stats.push((pass, CacheStats::default()));
+                stats.len() - 1
+            }
+        };
+        if hit {
+            stats[index].1.hits += 1;
+        } else {
+            stats[index].1.misses += 1;
+        }
+    }
+}
+
+impl Default for LineCache {
+    fn default() -> Self {
+        LineCache::new()
+    }
+}
+
+/// A pass that looks up its results in a `LineCache` before running.
+pub struct Cached<'a> {
+    pass: &'a Pass,
+    cache: &'a LineCache,
+}
+
+impl<'a> fmt::Debug for Cached<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.pass.fmt(f)
+    }
+}
+
+impl<'a> LinePass for Cached<'a> {
+    type Hint = Hint;
+    fn run(&self, clue: &[usize], line: &dyn Line) -> Vec<Box<Self::Hint>> {
+        let key = Key::new(self.pass, clue, line);
+        if let Some(hints) = self.cache.entries.lock().unwrap().get(&key) {
+            self.cache.record(key.pass, true);
+            return hints.iter().cloned().map(Box::new).collect();
+        }
+        self.cache.record(key.pass, false);
+        let hints = self.pass.run(clue, line);
+        let entry = hints.iter().map(|hint| (**hint).clone()).collect();
+        self.cache.entries.lock().unwrap().insert(key, entry);
+        hints
+    }
+    /// Colored lines are not cached, as the key only covers filled and
+    /// crossed cells.
+    fn run_colored(
+        &self,
+        clue: &[usize],
+        colors: &[usize],
+        line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>> {
+        self.pass.run_colored(clue, colors, line)
+    }
+}
+
+impl fmt::Display for LineCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, stats) in self.stats() {
+            let total = stats.hits + stats.misses;
+            writeln!(
+                f,
+                "{: <10} {: >8} hits {: >8} misses ({:.1}% hits)",
+                name,
+                stats.hits,
+                stats.misses,
+                100.0 * stats.hits as f64 / total as f64
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+extern crate fixedbitset;
+#[macro_use]
+extern crate itertools;
+extern crate pest;
+#[macro_use]
+extern crate pest_derive;
+#[cfg(test)]
+extern crate proptest;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+pub mod accounting;
+pub mod bench;
+pub mod cache;
+pub mod lookahead;
+pub mod minizinc;
+#[cfg(test)]
+mod oracle;
+pub mod parser;
+pub mod pass;
+pub mod provenance;
+pub mod puzzle;
+pub mod sat;
+pub mod server;
+pub mod solver;
+pub mod stats;
+pub mod triddler;
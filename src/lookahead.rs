@@ -0,0 +1,229 @@
+use fixedbitset::FixedBitSet;
+
+use parser::Cell;
+use puzzle;
+use puzzle::Axis;
+use puzzle::Line;
+use puzzle::LineHint;
+use puzzle::LineMut;
+use puzzle::LinePass;
+use puzzle::LinePassExt;
+use puzzle::Puzzle;
+use solver::Pass;
+
+/// Rounds of line passes over both axes that an assumption is followed for.
+const DEPTH: usize = 3;
+
+/// A cell that cannot have the assumed value.
+///
+/// Assuming the value and running the line passes for a few rounds made the
+/// line `broken` contradict its clue, so the cell gets the other value. The
+/// passes were run on the lines in `read`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lookahead {
+    cell: usize,
+    assumed: Cell,
+    broken: (Axis, usize),
+    read: Vec<(Axis, usize)>,
+}
+
+impl Lookahead {
+    /// The lines the assumption was followed through, which the hint
+    /// depends on along with its own line.
+    pub fn read(&self) -> &[(Axis, usize)] {
+        &self.read
+    }
+}
+
+impl LineHint for Lookahead {
+    fn check(&self, line: &dyn Line) -> bool {
+        match self.assumed {
+            Cell::Filled => !line.is_crossed(self.cell),
+            _ => !line.is_filled(self.cell),
+        }
+    }
+    fn apply(&self, line: &mut dyn LineMut) {
+        match self.assumed {
+            Cell::Filled => line.cross(self.cell),
+            _ => line.fill(self.cell),
+        };
+    }
+}
+
+/// A value tentatively given to a cell.
+#[derive(Debug)]
+struct Assumption {
+    cell: usize,
+    value: Cell,
+}
+
+impl LineHint for Assumption {
+    fn check(&self, line: &dyn Line) -> bool {
+        match self.value {
+            Cell::Filled => !line.is_filled(self.cell),
+            _ => !line.is_crossed(self.cell),
+        }
+    }
+    fn apply(&self, line: &mut dyn LineMut) {
+        match self.value {
+            Cell::Filled => line.fill(self.cell),
+            _ => line.cross(self.cell),
+        };
+    }
+}
+
+/// Tries both values for the undecided cells of a line and keeps the ones
+/// that lead to a contradiction elsewhere.
+///
+/// This needs the whole puzzle rather than a single line, so `run` finds
+/// nothing and the solver calls `run_puzzle` instead. Since a change anywhere
+/// can let it find something on any line, the schedules hand it every line
+/// again after each change.
+#[derive(Debug)]
+pub struct LookaheadPass;
+
+impl LinePass for LookaheadPass {
+    type Hint = Lookahead;
+    fn run(&self, _clue: &[usize], _line: &dyn Line) -> Vec<Box<Self::Hint>> {
+        vec![]
+    }
+    /// Like `run`, this finds nothing. Colored puzzles are probed by
+    /// `run_puzzle` as well.
+    fn run_colored(
+        &self,
+        _clue: &[usize],
+        _colors: &[usize],
+        _line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>> {
+        vec![]
+    }
+}
+
+impl LookaheadPass {
+    /// Follows each assumption with the line passes among `passes`. One-shot
+    /// passes are left out, as they have nothing to add after the first
+    /// round of the solve.
+    pub fn run_puzzle(
+        &self,
+        passes: &[Pass],
+        axis: &Axis,
+        puzzle: &Puzzle,
+        lines: &FixedBitSet,
+    ) -> Vec<puzzle::Hint<Lookahead>> {
+        let passes: Vec<&Pass> = passes
+            .iter()
+            .filter(|pass| !pass.is_one_shot())
+            .filter(|pass| !matches!(pass, Pass::Lookahead(_)))
+            .collect();
+        let len = match axis {
+            Axis::Horz => puzzle.width(),
+            Axis::Vert => puzzle.height(),
+        };
+        let mut probe = puzzle.without_history();
+        let mut hints = vec![];
+        for line in lines.ones() {
+            if puzzle.is_finished(*axis, line) {
+                continue;
+            }
+            for cell in 0..len {
+                let (x, y) = at(*axis, line, cell);
+                if puzzle.cell(x, y) != Cell::Undecided {
+                    continue;
+                }
+                for value in &[Cell::Filled, Cell::Crossed] {
+                    let assumption = Assumption {
+                        cell,
+                        value: *value,
+                    };
+                    if let Some(lookahead) = assume(&mut probe, &passes, *axis, line, assumption) {
+                        hints.push(puzzle::Hint::new(*axis, line, lookahead));
+                        break;
+                    }
+                }
+            }
+        }
+        hints
+    }
+}
+
+/// The grid position of a cell of a line.
+fn at(axis: Axis, line: usize, cell: usize) -> (usize, usize) {
+    match axis {
+        Axis::Horz => (cell, line),
+        _ => (line, cell),
+    }
+}
+
+/// Applies the assumption to the puzzle and runs the passes on the lines that
+/// change, returning a hint if some line turns out contradictory. The puzzle
+/// is restored afterwards.
+fn assume(
+    probe: &mut Puzzle,
+    passes: &[&Pass],
+    axis: Axis,
+    line: usize,
+    assumption: Assumption,
+) -> Option<Lookahead> {
+    let (cell, assumed) = (assumption.cell, assumption.value);
+    let snapshot = probe.snapshot();
+    let mut read = [
+        FixedBitSet::with_capacity(probe.height()),
+        FixedBitSet::with_capacity(probe.width()),
+    ];
+    let broken = follow(probe, passes, axis, line, assumption, &mut read);
+    probe.restore(snapshot);
+    probe.take_changes();
+    let read = read[0]
+        .ones()
+        .map(|line| (Axis::Horz, line))
+        .chain(read[1].ones().map(|line| (Axis::Vert, line)))
+        .collect();
+    broken.map(|broken| Lookahead {
+        cell,
+        assumed,
+        broken,
+        read,
+    })
+}
+
+fn follow(
+    probe: &mut Puzzle,
+    passes: &[&Pass],
+    axis: Axis,
+    line: usize,
+    assumption: Assumption,
+    read: &mut [FixedBitSet; 2],
+) -> Option<(Axis, usize)> {
+    puzzle::Hint::new(axis, line, assumption).apply(probe);
+    let changes = probe.take_changes();
+    let mut dirty = [changes.horz, changes.vert];
+    for _ in 0..DEPTH {
+        for (a, axis) in [Axis::Horz, Axis::Vert].iter().enumerate() {
+            let lines = dirty[a].clone();
+            dirty[a].clear();
+            read[a].extend(lines.ones());
+            for pass in passes {
+                for hint in pass.run_puzzle(axis, probe, &lines) {
+                    hint.apply(probe);
+                }
+            }
+            let len = match axis {
+                Axis::Horz => probe.width(),
+                _ => probe.height(),
+            };
+            let broken = lines.ones().find(|line| {
+                (0..len).any(|cell| {
+                    let (x, y) = at(*axis, *line, cell);
+                    probe.cell(x, y) == Cell::Impossible
+                })
+            });
+            if let Some(line) = broken {
+                return Some((*axis, line));
+            }
+            let changes = probe.take_changes();
+            dirty[0].extend(changes.horz.ones());
+            dirty[1].extend(changes.vert.ones());
+        }
+    }
+    None
+}
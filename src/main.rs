@@ -1,31 +1,39 @@
-extern crate fixedbitset;
-#[macro_use]
-extern crate itertools;
-extern crate pest;
-#[macro_use]
-extern crate pest_derive;
+extern crate nono;
 extern crate structopt;
 
-mod parser;
-mod pass;
-mod puzzle;
-
+use std::fs;
 use std::io;
 use std::io::BufRead;
+use std::io::Write;
+use std::net::TcpListener;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::thread;
 
-use parser::NonoParser;
-use parser::Rule;
-use pass::ContinuousRangeHint;
-use pass::ContinuousRangePass;
-use pass::CrowdedClue;
-use pass::CrowdedCluePass;
-use pass::DiscreteRangeHint;
-use pass::DiscreteRangePass;
-use pest::Parser;
-use puzzle::Axis;
-use puzzle::LineMut;
-use puzzle::LinePassExt;
-use puzzle::Theme;
+use nono::bench::Bench;
+use nono::cache::LineCache;
+use nono::minizinc;
+use nono::minizinc::Model;
+use nono::parser;
+use nono::parser::Cell;
+use nono::provenance;
+use nono::provenance::Position;
+use nono::puzzle;
+use nono::puzzle::Board;
+use nono::puzzle::Satisfied;
+use nono::puzzle::Theme;
+use nono::sat::Cnf;
+use nono::server;
+use nono::solver;
+use nono::solver::Observer;
+use nono::solver::Pass;
+use nono::solver::Step;
+use nono::solver::Strategy;
+use nono::stats::PassStats;
+use nono::stats::Stats;
+use nono::triddler;
+use nono::triddler::Triddler;
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
@@ -33,182 +41,391 @@ use structopt::StructOpt;
 /// A nonogram hint dispenser
 ///
 /// Available display themes: ascii, unicode, brief
+///
+/// Available passes: crowded, complete, edge, gap, ownership, continuous, discrete, lookahead
+///
+/// Available strategies: restart, round-robin, dirty
 struct Opt {
     /// Select display theme
     #[structopt(short = "t", long = "theme", default_value = "unicode")]
     theme: Theme,
+
+    /// Show placed clue numbers: plain, dim or strike
+    #[structopt(long = "satisfied", default_value = "plain")]
+    satisfied: Satisfied,
+
+    /// Select passes, cheapest first
+    #[structopt(
+        short = "p",
+        long = "passes",
+        default_value = "crowded,complete,edge,gap,ownership,continuous,discrete",
+        raw(use_delimiter = "true")
+    )]
+    passes: Vec<Pass>,
+
+    /// Select scheduling strategy
+    #[structopt(short = "s", long = "strategy", default_value = "restart")]
+    strategy: Strategy,
+
+    /// Remember line results and report cache statistics
+    #[structopt(short = "c", long = "cache")]
+    cache: bool,
+
+    /// Run passes on this many lines at once
+    #[structopt(short = "j", long = "jobs", default_value = "1")]
+    jobs: usize,
+
+    /// Print statistics on the work done by each pass
+    #[structopt(long = "stats")]
+    stats: bool,
+
+    /// Solve this many puzzles at once
+    #[structopt(long = "puzzle-jobs", default_value = "1")]
+    puzzle_jobs: usize,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Debug)]
-enum Hint {
-    CrowdedClue(CrowdedClue),
-    ContinuousRange(ContinuousRangeHint),
-    DiscreteRange(DiscreteRangeHint),
+#[derive(StructOpt)]
+enum Command {
+    /// Serve hints over HTTP on localhost
+    ///
+    /// Endpoints: POST /hint, POST /solve, POST /check. Request bodies are
+    /// puzzles in the one-line format or as JSON objects with the keys
+    /// vert_clues, horz_clues and (optionally) grid.
+    #[structopt(name = "serve")]
+    Serve {
+        /// Port to listen on
+        #[structopt(short = "p", long = "port", default_value = "8080")]
+        port: u16,
+    },
+
+    /// Time the passes on the puzzles given on stdin
+    ///
+    /// Every puzzle is solved once per round. Reports the best and mean time
+    /// per puzzle, and the time spent in each pass along with how many times
+    /// it was run and on how many lines.
+    #[structopt(name = "bench")]
+    Bench {
+        /// Solve each puzzle this many times
+        #[structopt(short = "r", long = "rounds", default_value = "10")]
+        rounds: usize,
+    },
+
+    /// Explain how a cell got its value in the puzzles given on stdin
+    ///
+    /// Solves each puzzle and prints the chain of hints that led to the cell:
+    /// each hint along with the cells it decided and the cells of its line it
+    /// started from.
+    #[structopt(name = "why")]
+    Why {
+        /// The cell as X,Y: column and row, counting from 1
+        cell: Position,
+    },
+
+    /// Write the puzzles given on stdin in another format
+    #[structopt(name = "export")]
+    Export {
+        /// Encode clues and decided cells as DIMACS CNF
+        #[structopt(long = "cnf")]
+        cnf: bool,
+
+        /// Write a MiniZinc model with a regular constraint per line
+        #[structopt(long = "mzn")]
+        mzn: bool,
+
+        /// Solve and write the graph of hints and the earlier hints they
+        /// depend on in Graphviz DOT
+        #[structopt(long = "dot")]
+        dot: bool,
+    },
+
+    /// Read solutions to the models of the puzzles given on stdin
+    ///
+    /// Prints each puzzle in the one-line format with the solution as its
+    /// grid.
+    #[structopt(name = "import")]
+    Import {
+        /// Read MiniZinc solver output from this file, one solution per puzzle
+        #[structopt(long = "mzn")]
+        mzn: String,
+    },
+
+    /// Solve the puzzles given on stdin with the built-in SAT solver
+    ///
+    /// Only monochrome puzzles are supported. Prints the solved puzzle, or
+    /// that there is no solution.
+    #[structopt(name = "sat")]
+    Sat,
+}
+
+fn solve<W: Write>(
+    out: &mut W,
+    line: &str,
+    opt: &Opt,
+    cache: Option<&LineCache>,
+    totals: &Mutex<Stats>,
+) -> io::Result<()> {
+    if line.trim_start().starts_with('<') {
+        let ast: parser::Triddler = line.parse().unwrap_or_else(|e| panic!("{}", e));
+        let mut triddler = Triddler::try_from_ast(ast).unwrap_or_else(|e| panic!("{}", e));
+        return solve_board(out, &mut triddler, opt, cache, totals, |triddler| {
+            triddler::View::new(triddler, &opt.theme).to_string()
+        });
+    }
+    let ast: parser::Puzzle = line.parse().unwrap_or_else(|e| panic!("{}", e));
+    let mut puzzle = puzzle::Puzzle::try_from_ast(ast).unwrap_or_else(|e| panic!("{}", e));
+    solve_board(out, &mut puzzle, opt, cache, totals, |puzzle| {
+        opt.theme
+            .view(puzzle)
+            .with_satisfied(opt.satisfied)
+            .to_string()
+    })
 }
 
-use puzzle::Line;
+/// Prints the progress of a solve: the puzzle with `view` at the start and
+/// after every step that finds something, and the statistics at the end.
+struct Progress<'o, W: 'o, V> {
+    out: &'o mut W,
+    opt: &'o Opt,
+    view: V,
+    stats: Stats,
+    /// The first error writing to `out`, after which the solve is stopped.
+    result: io::Result<()>,
+}
 
-impl puzzle::LineHint for Hint {
-    fn check(&self, line: &Line) -> bool {
-        match self {
-            Hint::CrowdedClue(inner) => inner.check(line),
-            Hint::ContinuousRange(inner) => inner.check(line),
-            Hint::DiscreteRange(inner) => inner.check(line),
+impl<'o, W: Write, V> Progress<'o, W, V> {
+    fn print_step<B>(&mut self, step: &Step<B::Axis>, puzzle: &B) -> io::Result<()>
+    where
+        B: Board,
+        V: Fn(&B) -> String,
+    {
+        if self.opt.theme != Theme::Brief {
+            writeln!(self.out, "{:?} {:?} ({})", step.pass, step.axis, step.round)?;
+            for hint in &step.hints {
+                writeln!(self.out, "{:?}", hint)?;
+            }
         }
-    }
-    fn apply(&self, line: &mut LineMut) {
-        match self {
-            Hint::CrowdedClue(inner) => inner.apply(line),
-            Hint::ContinuousRange(inner) => inner.apply(line),
-            Hint::DiscreteRange(inner) => inner.apply(line),
+        if !step.hints.is_empty() {
+            writeln!(self.out, "{}", (self.view)(puzzle))?;
         }
+        Ok(())
     }
 }
 
-#[derive(Debug)]
-enum Pass {
-    CrowdedClue(CrowdedCluePass),
-    ContinuousRange(ContinuousRangePass),
-    DiscreteRange(DiscreteRangePass),
-}
+impl<'o, W, B, V> Observer<B> for Progress<'o, W, V>
+where
+    W: Write,
+    B: Board,
+    V: Fn(&B) -> String,
+{
+    fn started(&mut self, puzzle: &B) {
+        self.result = writeln!(self.out, "{}", (self.view)(puzzle));
+    }
 
-impl puzzle::LinePass for Pass {
-    type Hint = Hint;
-    fn run(&self, clue: &[usize], line: &Line) -> Vec<Box<Self::Hint>> {
-        match self {
-            Pass::CrowdedClue(inner) => inner
-                .run(clue, line)
-                .into_iter()
-                .map(|hint| Box::new(Hint::CrowdedClue(*hint)))
-                .collect(),
-            Pass::ContinuousRange(inner) => inner
-                .run(clue, line)
-                .into_iter()
-                .map(|hint| Box::new(Hint::ContinuousRange(*hint)))
-                .collect(),
-            Pass::DiscreteRange(inner) => inner
-                .run(clue, line)
-                .into_iter()
-                .map(|hint| Box::new(Hint::DiscreteRange(*hint)))
-                .collect(),
+    fn stepped(&mut self, step: &Step<B::Axis>, puzzle: &B) -> bool {
+        self.stats.record(
+            step.pass.name(),
+            step.axis,
+            &PassStats {
+                invocations: 1,
+                hints: step.hints.len(),
+                cells: step.decided,
+                time: step.time,
+            },
+        );
+        if self.result.is_ok() {
+            self.result = self.print_step(step, puzzle);
+        }
+        self.result.is_ok()
+    }
+
+    fn finished(&mut self, _puzzle: &B, _rounds: usize) {
+        if self.result.is_ok() && self.opt.stats {
+            self.result = writeln!(self.out, "{}", self.stats);
         }
     }
 }
 
-struct Solver<'a> {
-    cur_p: usize,
-    cur_a: usize,
-    fail_count: usize,
-    passes: &'a [Pass],
+/// Solves a puzzle of either kind, showing it with `view` after every step
+/// that finds something.
+fn solve_board<W, B, V>(
+    out: &mut W,
+    puzzle: &mut B,
+    opt: &Opt,
+    cache: Option<&LineCache>,
+    totals: &Mutex<Stats>,
+    view: V,
+) -> io::Result<()>
+where
+    W: Write,
+    B: Board,
+    V: Fn(&B) -> String,
+{
+    let mut progress = Progress {
+        out,
+        opt,
+        view,
+        stats: Stats::new(),
+        result: Ok(()),
+    };
+    let schedule = opt.strategy.schedule(&opt.passes, puzzle);
+    solver::observe(
+        puzzle,
+        &opt.passes,
+        schedule,
+        cache,
+        opt.jobs,
+        &mut progress,
+    );
+    totals.lock().unwrap().merge(&progress.stats);
+    progress.result
 }
 
-impl<'a> Solver<'a> {
-    fn new(passes: &'a [Pass]) -> Self {
-        Solver {
-            cur_p: 0,
-            cur_a: 0,
-            fail_count: 0,
-            passes,
-        }
-    }
+fn main() {
+    let opt = Opt::from_args();
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
 
-    fn initial(&mut self) -> (&'a Pass, Axis) {
-        (&self.passes[self.cur_p], Axis::get(self.cur_a).unwrap())
+    if let Some(Command::Serve { port }) = opt.command {
+        let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|e| panic!("{}", e));
+        server::serve(&listener, &opt.passes, opt.strategy);
+        return;
     }
 
-    fn succeeded(&mut self) -> Option<(&'a Pass, Axis)> {
-        self.fail_count = 0;
+    if let Some(Command::Import { ref mzn }) = opt.command {
+        let output = fs::read_to_string(mzn).unwrap_or_else(|e| panic!("{}: {}", mzn, e));
+        let mut solutions = output.split("----------");
+        for line in stdin.lock().lines() {
+            let ast: parser::Puzzle = line.unwrap().parse().unwrap_or_else(|e| panic!("{}", e));
+            let solution = solutions
+                .next()
+                .unwrap_or_else(|| panic!("{}: fewer solutions than puzzles", mzn));
+            let imported = minizinc::import(ast, solution).unwrap_or_else(|e| panic!("{}", e));
+            println!("{}", imported);
+        }
+        return;
+    }
 
-        let last_p = self.cur_p;
-        if self.cur_p > 1 {
-            self.cur_p = 1;
-            self.next(last_p)
-        } else {
-            self.next(last_p)
+    if let Some(Command::Sat) = opt.command {
+        for line in stdin.lock().lines() {
+            let ast: parser::Puzzle = line.unwrap().parse().unwrap_or_else(|e| panic!("{}", e));
+            let cnf = Cnf::encode(&ast).unwrap_or_else(|e| panic!("{}", e));
+            match cnf.solve() {
+                Some(model) => {
+                    let puzzle = puzzle::Puzzle::try_from_ast(ast)
+                        .and_then(|puzzle| puzzle.with_grid(cnf.grid(&model)))
+                        .unwrap_or_else(|e| panic!("{}", e));
+                    println!("{}", opt.theme.view(&puzzle).with_satisfied(opt.satisfied));
+                }
+                None => println!("no solution"),
+            }
         }
+        return;
     }
 
-    fn failed(&mut self) -> Option<(&'a Pass, Axis)> {
-        self.fail_count += 1;
+    let cache = if opt.cache {
+        Some(LineCache::new())
+    } else {
+        None
+    };
 
-        let last_p = self.cur_p;
-        self.next(last_p)
+    if let Some(Command::Why { cell }) = opt.command {
+        for line in stdin.lock().lines() {
+            let ast: parser::Puzzle = line.unwrap().parse().unwrap_or_else(|e| panic!("{}", e));
+            let mut puzzle = puzzle::Puzzle::try_from_ast(ast).unwrap_or_else(|e| panic!("{}", e));
+            if cell.x >= puzzle.width() || cell.y >= puzzle.height() {
+                panic!("{} is outside the puzzle", cell);
+            }
+            let schedule = opt.strategy.schedule(&opt.passes, &puzzle);
+            let provenance =
+                provenance::run(&mut puzzle, &opt.passes, schedule, cache.as_ref(), opt.jobs);
+            println!("{}", opt.theme.view(&puzzle).with_satisfied(opt.satisfied));
+            if provenance.step(cell.x, cell.y).is_some() {
+                for step in provenance.why(cell.x, cell.y) {
+                    println!("{}", step);
+                }
+            } else if puzzle.cell(cell.x, cell.y) == Cell::Undecided {
+                println!("{} is undecided", cell);
+            } else {
+                println!("{} was given", cell);
+            }
+        }
+        return;
     }
 
-    fn next(&mut self, last_p: usize) -> Option<(&'a Pass, Axis)> {
-        if self.fail_count >= 2 {
-            self.cur_p += 1;
-            self.fail_count = 0;
+    if let Some(Command::Export { cnf, mzn, dot }) = opt.command {
+        if [cnf, mzn, dot].iter().filter(|format| **format).count() != 1 {
+            panic!("select one export format: --cnf, --mzn or --dot");
         }
-
-        self.cur_a = 1 - self.cur_a;
-        if self.cur_a == 0 {
-            if let Some(Pass::CrowdedClue(_)) = self.passes.get(last_p) {
-                self.cur_p = 1;
+        for line in stdin.lock().lines() {
+            let ast: parser::Puzzle = line.unwrap().parse().unwrap_or_else(|e| panic!("{}", e));
+            if cnf {
+                let cnf = Cnf::encode(&ast).unwrap_or_else(|e| panic!("{}", e));
+                print!("{}", cnf);
+            } else if mzn {
+                let model = Model::encode(&ast).unwrap_or_else(|e| panic!("{}", e));
+                print!("{}", model);
+            } else {
+                let mut puzzle =
+                    puzzle::Puzzle::try_from_ast(ast).unwrap_or_else(|e| panic!("{}", e));
+                let schedule = opt.strategy.schedule(&opt.passes, &puzzle);
+                let provenance =
+                    provenance::run(&mut puzzle, &opt.passes, schedule, cache.as_ref(), opt.jobs);
+                print!("{}", provenance.dot());
             }
         }
+        return;
+    }
 
-        if let Some(pass) = self.passes.get(self.cur_p) {
-            return Some((pass, Axis::get(self.cur_a).unwrap()));
-        } else {
-            None
+    if let Some(Command::Bench { rounds }) = opt.command {
+        let mut bench = Bench::new(rounds);
+        for line in stdin.lock().lines() {
+            let line = line.unwrap();
+            bench
+                .run(&line, &opt.passes, opt.strategy, cache.as_ref(), opt.jobs)
+                .unwrap_or_else(|e| panic!("{}", e));
         }
+        print!("{}", bench);
+        return;
     }
-}
 
-fn main() {
-    let opt = Opt::from_args();
+    let totals = Mutex::new(Stats::new());
 
-    let stdin = io::stdin();
-    let passes: [Pass; 3] = [
-        Pass::CrowdedClue(CrowdedCluePass),
-        Pass::ContinuousRange(ContinuousRangePass),
-        Pass::DiscreteRange(DiscreteRangePass),
-    ];
-    for line in stdin.lock().lines() {
-        let line = line.unwrap();
-        let ast = NonoParser::parse(Rule::puzzle, &line)
-            .unwrap_or_else(|e| panic!("{}", e))
-            .next()
-            .map(parser::Puzzle::from)
-            .unwrap();
-        match puzzle::Puzzle::try_from_ast(ast) {
-            Ok(mut puzzle) => {
-                let mut solver = Solver::new(&passes);
-
-                println!("{}", opt.theme.view(&puzzle));
-
-                let mut next_pass = Some(solver.initial());
-                let mut pass_counter = 0;
-                while let Some((pass, axis)) = next_pass {
-                    if puzzle.is_complete() {
+    if opt.puzzle_jobs > 1 {
+        let lines: Vec<String> = stdin.lock().lines().map(|line| line.unwrap()).collect();
+        let outputs: Vec<Mutex<Vec<u8>>> = lines.iter().map(|_| Mutex::new(vec![])).collect();
+        let next_line = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..opt.puzzle_jobs {
+                scope.spawn(|| loop {
+                    let i = next_line.fetch_add(1, Ordering::SeqCst);
+                    if i >= lines.len() {
                         break;
                     }
-
-                    pass_counter += 1;
-                    let hints = pass.run_puzzle(&axis, &puzzle);
-                    for hint in &hints {
-                        hint.apply(&mut puzzle);
-                    }
-
-                    if opt.theme != Theme::Brief {
-                        println!("{:?} {:?} ({})", pass, axis, pass_counter);
-                        for hint in &hints {
-                            println!("{:?}", hint);
-                        }
-                    }
-                    if !hints.is_empty() {
-                        println!("{}", opt.theme.view(&puzzle));
-                    }
-
-                    next_pass = if hints.is_empty() {
-                        solver.failed()
-                    } else {
-                        solver.succeeded()
-                    };
-                }
+                    let mut output = outputs[i].lock().unwrap();
+                    solve(&mut *output, &lines[i], &opt, cache.as_ref(), &totals).unwrap();
+                });
             }
-            Err(e) => panic!("{}", e),
+        });
+        let mut stdout = stdout.lock();
+        for output in outputs {
+            stdout.write_all(&output.into_inner().unwrap()).unwrap();
+        }
+    } else {
+        for line in stdin.lock().lines() {
+            let line = line.unwrap();
+            solve(&mut stdout.lock(), &line, &opt, cache.as_ref(), &totals).unwrap();
         }
     }
+
+    if opt.stats {
+        println!("All puzzles");
+        print!("{}", totals.into_inner().unwrap());
+    }
+    if let Some(cache) = cache {
+        print!("{}", cache);
+    }
 }
@@ -0,0 +1,282 @@
+//! Constraint models of monochrome puzzles in MiniZinc.
+//!
+//! Cells are variables in `1..2`, 1 for crossed and 2 for filled. Each row
+//! and column is constrained by `regular` with an automaton that accepts
+//! exactly the lines matching its clue, and decided cells of the grid are
+//! fixed. The model has no output item, so solvers print the assignment of
+//! `cell` as `cell = array2d(1..h, 1..w, [...]);`, which `import` reads back.
+
+use std::fmt;
+
+use parser;
+use parser::Cell;
+use parser::Grid;
+use parser::GridLine;
+
+/// A MiniZinc model of a puzzle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Model {
+    width: usize,
+    height: usize,
+    horz_clues: Vec<Vec<usize>>,
+    vert_clues: Vec<Vec<usize>>,
+    /// Cells of the grid that are fixed, and whether they are filled.
+    fixed: Vec<(usize, usize, bool)>,
+}
+
+impl Model {
+    pub fn encode(ast: &parser::Puzzle) -> Result<Model, String> {
+        let clues = |clues: &parser::ClueList| -> Result<Vec<Vec<usize>>, String> {
+            clues
+                .0
+                .iter()
+                .map(|clue| {
+                    if clue.is_colored() {
+                        Err("only monochrome puzzles can be modelled".to_string())
+                    } else if clue.has_zero() {
+                        Err("clues can not hold a zero".to_string())
+                    } else {
+                        Ok(clue.numbers.clone())
+                    }
+                })
+                .collect()
+        };
+        let mut model = Model {
+            width: ast.vert_clues.0.len(),
+            height: ast.horz_clues.0.len(),
+            horz_clues: clues(&ast.horz_clues)?,
+            vert_clues: clues(&ast.vert_clues)?,
+            fixed: vec![],
+        };
+        if let Some(grid) = &ast.grid {
+            check_size(grid, model.width, model.height)?;
+            for (y, grid_line) in grid.0.iter().enumerate() {
+                for (x, cell) in grid_line.0.iter().enumerate() {
+                    match cell {
+                        Cell::Filled => model.fixed.push((x, y, true)),
+                        Cell::Crossed | Cell::Masked => model.fixed.push((x, y, false)),
+                        Cell::Impossible => {
+                            model.fixed.push((x, y, true));
+                            model.fixed.push((x, y, false));
+                        }
+                        Cell::Undecided => {}
+                        Cell::Colored(_) | Cell::Unresolved => {
+                            return Err(format!(
+                                "cell {} can not be modelled in a monochrome puzzle",
+                                cell
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(model)
+    }
+}
+
+fn check_size(grid: &Grid, width: usize, height: usize) -> Result<(), String> {
+    if grid.0.len() != height || grid.0.iter().any(|line| line.0.len() != width) {
+        return Err(format!("grid is not {} by {} cells", width, height));
+    }
+    Ok(())
+}
+
+/// An automaton accepting the lines that match a clue, with cells read as 1
+/// for crossed and 2 for filled.
+///
+/// Returns the next state for each state and cell, counting states from 1
+/// and using 0 for failure, along with the accepting states. State 1 is the
+/// start.
+fn automaton(clue: &[usize]) -> (Vec<[usize; 2]>, Vec<usize>) {
+    let mut states = vec![];
+    for (i, number) in clue.iter().enumerate() {
+        // a state before the block, where any number of crosses may come
+        let gap = states.len() + 1;
+        states.push([gap, gap + 1]);
+        for j in 1..=*number {
+            let next = if j < *number {
+                [0, gap + j + 1]
+            } else {
+                [0, 0]
+            };
+            states.push(next);
+        }
+        if i > 0 {
+            // the end of the previous block leads here on a cross
+            states[gap - 2][0] = gap;
+        }
+    }
+    // a state after the last block, where only crosses may come
+    let last = states.len();
+    let end = last + 1;
+    states.push([end, 0]);
+    if last > 0 {
+        states[last - 1][0] = end;
+        (states, vec![last, end])
+    } else {
+        (states, vec![end])
+    }
+}
+
+fn write_regular(f: &mut fmt::Formatter, cells: &str, clue: &[usize]) -> fmt::Result {
+    let (states, accepting) = automaton(clue);
+    let table = states
+        .iter()
+        .map(|next| format!("{}, {}", next[0], next[1]))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    let accepting = accepting
+        .iter()
+        .map(|state| state.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(
+        f,
+        "constraint regular({}, {}, 2, [| {} |], 1, {{{}}});",
+        cells,
+        states.len(),
+        table,
+        accepting
+    )
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "% {} by {} cells, cell[y, x] is 1 for crossed and 2 for filled",
+            self.width, self.height
+        )?;
+        writeln!(f, "include \"regular.mzn\";")?;
+        writeln!(
+            f,
+            "array[1..{}, 1..{}] of var 1..2: cell;",
+            self.height, self.width
+        )?;
+        for (y, clue) in self.horz_clues.iter().enumerate() {
+            let cells = format!("[cell[{}, x] | x in 1..{}]", y + 1, self.width);
+            write_regular(f, &cells, clue)?;
+        }
+        for (x, clue) in self.vert_clues.iter().enumerate() {
+            let cells = format!("[cell[y, {}] | y in 1..{}]", x + 1, self.height);
+            write_regular(f, &cells, clue)?;
+        }
+        for (x, y, filled) in &self.fixed {
+            let value = if *filled { 2 } else { 1 };
+            writeln!(f, "constraint cell[{}, {}] = {};", y + 1, x + 1, value)?;
+        }
+        writeln!(f, "solve satisfy;")
+    }
+}
+
+/// Reads the assignment of `cell` printed by a solver for the model of the
+/// puzzle, and returns the puzzle with the assigned cells as its grid. Cells
+/// outside the board stay that way.
+pub fn import<'a>(ast: parser::Puzzle<'a>, solution: &str) -> Result<parser::Puzzle<'a>, String> {
+    let width = ast.vert_clues.0.len();
+    let height = ast.horz_clues.0.len();
+    if solution.contains("=====UNSATISFIABLE=====") {
+        return Err("the model has no solution".to_string());
+    }
+    let values = solution
+        .find("cell")
+        .map(|start| &solution[start..])
+        .and_then(|rest| rest.find('[').map(|open| &rest[open + 1..]))
+        .and_then(|rest| rest.find(']').map(|close| &rest[..close]))
+        .ok_or_else(|| "no assignment of cell found".to_string())?;
+    let values = values
+        .split(',')
+        .map(|value| match value.trim() {
+            "1" => Ok(Cell::Crossed),
+            "2" => Ok(Cell::Filled),
+            other => Err(format!("unexpected cell value {}", other)),
+        })
+        .collect::<Result<Vec<Cell>, String>>()?;
+    if values.len() != width * height {
+        return Err(format!(
+            "{} cells assigned to a {} by {} puzzle",
+            values.len(),
+            width,
+            height
+        ));
+    }
+    let mut grid = Grid(
+        values
+            .chunks(width)
+            .map(|cells| GridLine(cells.to_vec()))
+            .collect(),
+    );
+    if let Some(given) = &ast.grid {
+        check_size(given, width, height)?;
+        for (line, given_line) in grid.0.iter_mut().zip(&given.0) {
+            for (cell, given_cell) in line.0.iter_mut().zip(&given_line.0) {
+                if *given_cell == Cell::Masked {
+                    *cell = Cell::Masked;
+                }
+            }
+        }
+    }
+    Ok(parser::Puzzle {
+        vert_clues: ast.vert_clues,
+        horz_clues: ast.horz_clues,
+        grid: Some(grid),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use oracle;
+
+    fn accepts(clue: &[usize], line: &[bool]) -> bool {
+        let (states, accepting) = automaton(clue);
+        let mut state = 1;
+        for filled in line {
+            state = states[state - 1][*filled as usize];
+            if state == 0 {
+                return false;
+            }
+        }
+        accepting.contains(&state)
+    }
+
+    #[test]
+    fn automata() {
+        for len in 0..8 {
+            for bits in 0..1 << len {
+                let line: Vec<bool> = (0..len).map(|i| bits & (1 << i) != 0).collect();
+                let runs = oracle::runs(&line);
+                for clue in oracle::clues(len) {
+                    assert_eq!(accepts(&clue, &line), clue == runs, "{:?} {:?}", clue, line);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn model() {
+        let ast: parser::Puzzle = "[1;|1;|._;..]".parse().unwrap();
+        let model = format!("{}", Model::encode(&ast).unwrap());
+        assert!(model.contains(
+            "constraint regular([cell[1, x] | x in 1..2], 3, 2, [| 1, 2 | 3, 0 | 3, 0 |], 1, {2, 3});"
+        ));
+        assert!(model
+            .contains("constraint regular([cell[y, 2] | y in 1..2], 1, 2, [| 1, 0 |], 1, {1});"));
+        assert!(model.contains("constraint cell[1, 2] = 1;"));
+        assert!(Model::encode(&"[1r|1r]".parse().unwrap()).is_err());
+        assert!(Model::encode(&"[1;;1|1,0,1]".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn import_solution() {
+        let ast: parser::Puzzle = "[1;|1;|._;..]".parse().unwrap();
+        let solution = "cell = array2d(1..2, 1..2, [2, 1, 1, 1]);\n----------\n";
+        let imported = import(ast, solution).unwrap();
+        assert_eq!(format!("{}", imported), "[1;|1;|#_;xx]");
+        let ast: parser::Puzzle = "[1;|1;]".parse().unwrap();
+        assert!(import(ast, "=====UNSATISFIABLE=====\n").is_err());
+        let ast: parser::Puzzle = "[1;|1;]".parse().unwrap();
+        assert!(import(ast, "cell = array2d(1..1, 1..2, [2, 1]);").is_err());
+    }
+}
@@ -0,0 +1,549 @@
+//! Brute-force reference for checking that line passes are sound.
+//!
+//! Every placement of a clue on a line that agrees with the decided cells is
+//! enumerated. A hint is sound if every cell it fills is filled in all of
+//! those placements, and every cell it crosses is empty in all of them.
+
+use pest::Parser;
+
+use parser::Cell;
+use parser::GridLine;
+use parser::NonoParser;
+use parser::Rule;
+use puzzle::Grid;
+use puzzle::Line;
+use puzzle::LineHint;
+use puzzle::LineMut;
+use puzzle::LinePass;
+
+/// Enumerates the placements of `clue` that agree with `cells`.
+///
+/// Each placement is given as the filled state of every cell.
+pub fn placements(clue: &[usize], cells: &[Cell]) -> Vec<Vec<bool>> {
+    fn place(
+        clue: &[usize],
+        cells: &[Cell],
+        start: usize,
+        current: &mut Vec<bool>,
+        found: &mut Vec<Vec<bool>>,
+    ) {
+        let fits_empty = |range: ::std::ops::Range<usize>| {
+            range
+                .into_iter()
+                .all(|i| cells[i] == Cell::Undecided || cells[i] == Cell::Crossed)
+        };
+        match clue.split_first() {
+            None => {
+                if fits_empty(start..cells.len()) {
+                    let mut placement = current.clone();
+                    placement.resize(cells.len(), false);
+                    found.push(placement);
+                }
+            }
+            Some((number, rest)) => {
+                let needed: usize = rest.iter().map(|n| n + 1).sum::<usize>() + number;
+                let mut pos = start;
+                while pos + needed <= cells.len() {
+                    let end = pos + number;
+                    let fits = fits_empty(start..pos)
+                        && (pos..end)
+                            .all(|i| cells[i] == Cell::Undecided || cells[i] == Cell::Filled)
+                        && (end == cells.len() || fits_empty(end..end + 1));
+                    if fits {
+                        let len = current.len();
+                        current.resize(pos, false);
+                        current.resize(end, true);
+                        if end < cells.len() {
+                            current.push(false);
+                        }
+                        place(rest, cells, current.len(), current, found);
+                        current.truncate(len);
+                    }
+                    pos += 1;
+                }
+            }
+        }
+    }
+
+    let mut found = vec![];
+    place(clue, cells, 0, &mut vec![], &mut found);
+    found
+}
+
+fn grid(cells: &[Cell]) -> Grid {
+    let mut grid = Grid::new(cells.len(), 1);
+    for (i, cell) in cells.iter().enumerate() {
+        match cell {
+            Cell::Filled => {
+                grid.fill(i, 0);
+            }
+            Cell::Crossed => {
+                grid.cross(i, 0);
+            }
+            Cell::Impossible => {
+                grid.fill(i, 0);
+                grid.cross(i, 0);
+            }
+            Cell::Masked => grid.mask(i, 0),
+            Cell::Undecided => {}
+            Cell::Colored(_) | Cell::Unresolved => unreachable!(),
+        }
+    }
+    grid
+}
+
+/// A grid of a single line, given as cells in the one-line format.
+pub fn line_grid(s: &str) -> Grid {
+    if s.is_empty() {
+        return grid(&[]);
+    }
+    let cells = NonoParser::parse(Rule::grid_line, s)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .next()
+        .map(GridLine::from)
+        .unwrap();
+    grid(&cells.0)
+}
+
+/// Runs the pass on a line given as cells in the one-line format.
+pub fn hints<P: LinePass>(pass: &P, clue: &[usize], cells: &str) -> Vec<P::Hint> {
+    let mut grid = line_grid(cells);
+    let line = grid.horz_mut(0);
+    pass.run(clue, &line)
+        .into_iter()
+        .map(|hint| *hint)
+        .collect()
+}
+
+/// Checks every hint the pass emits for the line against the placements
+/// that agree with it.
+///
+/// Lines that no placement agrees with are contradictions, where any hint
+/// goes.
+pub fn check_pass<P: LinePass>(pass: &P, clue: &[usize], cells: &[Cell]) -> Result<(), String> {
+    let placements = placements(clue, cells);
+    if placements.is_empty() {
+        return Ok(());
+    }
+
+    let mut grid = grid(cells);
+    let hints = pass.run(clue, &grid.horz_mut(0));
+    for hint in hints {
+        let mut after = self::grid(cells);
+        hint.apply(&mut after.horz_mut(0));
+        let line = after.horz_mut(0);
+        for i in 0..cells.len() {
+            let wrong = match (&cells[i], line.get(i)) {
+                (Cell::Undecided, Cell::Filled) => placements.iter().any(|p| !p[i]),
+                (Cell::Undecided, Cell::Crossed) => placements.iter().any(|p| p[i]),
+                (_, Cell::Impossible) => true,
+                _ => false,
+            };
+            if wrong {
+                return Err(format!(
+                    "{:?} on clue {:?} and line {} decides cell {} wrongly with {:?}",
+                    pass,
+                    clue,
+                    cells.iter().map(|c| format!("{}", c)).collect::<String>(),
+                    i,
+                    hint
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A grid of a single colored line, with a palette of `colors` colors.
+///
+/// Each cell is given as a bit mask of what it can still be: bit 0 for
+/// background and bit `1 + c` for the color with palette index `c`.
+fn colored_grid(colors: usize, cells: &[u8]) -> Grid {
+    let palette = (0..colors).map(|c| (b'a' + c as u8) as char).collect();
+    let mut grid = Grid::colored(cells.len(), 1, palette);
+    {
+        let mut line = grid.horz_mut(0);
+        for (i, cell) in cells.iter().enumerate() {
+            let can_be_colored = cell >> 1 != 0;
+            if cell & 1 == 0 {
+                line.fill(i);
+                if !can_be_colored {
+                    line.cross(i);
+                }
+            } else if !can_be_colored {
+                line.cross(i);
+            }
+            for c in 0..colors {
+                if cell & (2 << c) == 0 {
+                    line.exclude(i, c);
+                }
+            }
+        }
+    }
+    grid
+}
+
+/// What a cell of a colored line can still be, as a bit mask.
+fn possible(line: &dyn Line, i: usize) -> u8 {
+    let mut cell = !line.is_filled(i) as u8;
+    if !line.is_crossed(i) {
+        for c in 0..line.colors() {
+            if !line.is_excluded(i, c) {
+                cell |= 2 << c;
+            }
+        }
+    }
+    cell
+}
+
+/// Enumerates the solutions of a colored clue that agree with `cells`.
+///
+/// Each solution gives every cell as a bit mask with a single bit set.
+pub fn colored_placements(clue: &[usize], colors: &[usize], cells: &[u8]) -> Vec<Vec<u8>> {
+    let used = cells.iter().fold(1, |acc, cell| acc | cell);
+    let choices = 8 - used.leading_zeros() as usize;
+    let mut found = vec![];
+    let mut solution = vec![1u8; cells.len()];
+    loop {
+        if solution.iter().zip(cells).all(|(s, c)| s & c != 0) {
+            let mut runs: Vec<(usize, u8)> = vec![];
+            let mut prev = 1;
+            for s in &solution {
+                if *s != 1 {
+                    match runs.last_mut() {
+                        Some((run, color)) if *color == *s && prev == *s => *run += 1,
+                        _ => runs.push((1, *s)),
+                    }
+                }
+                prev = *s;
+            }
+            let expected: Vec<(usize, u8)> = clue
+                .iter()
+                .zip(colors)
+                .map(|(number, c)| (*number, 2 << c))
+                .collect();
+            if runs == expected {
+                found.push(solution.clone());
+            }
+        }
+        // next solution, counting in base `choices`
+        let mut i = 0;
+        while i < solution.len() && solution[i] == 1 << (choices - 1) {
+            solution[i] = 1;
+            i += 1;
+        }
+        if i == solution.len() {
+            return found;
+        }
+        solution[i] <<= 1;
+    }
+}
+
+/// Checks every hint the pass emits for the colored line against the
+/// solutions that agree with it.
+///
+/// If `exact` is set, the hints together must also rule out everything that
+/// no solution has.
+pub fn check_colored_pass<P: LinePass>(
+    pass: &P,
+    clue: &[usize],
+    colors: &[usize],
+    palette: usize,
+    cells: &[u8],
+    exact: bool,
+) -> Result<(), String> {
+    let solutions = colored_placements(clue, colors, cells);
+    if solutions.is_empty() {
+        return Ok(());
+    }
+    let union: Vec<u8> = (0..cells.len())
+        .map(|i| solutions.iter().fold(0, |acc, s| acc | s[i]))
+        .collect();
+
+    let mut grid = colored_grid(palette, cells);
+    let hints = pass.run_colored(clue, colors, &grid.horz_mut(0));
+    let mut after = colored_grid(palette, cells);
+    for hint in hints {
+        hint.apply(&mut after.horz_mut(0));
+    }
+    let line = after.horz_mut(0);
+    for (i, expected) in union.iter().enumerate() {
+        let cell = possible(&line, i);
+        if expected & !cell != 0 || (exact && cell != *expected) {
+            return Err(format!(
+                "{:?} on clue {:?} colored {:?} and line {:?} leaves cell {} as {:b} instead of {:b}",
+                pass, clue, colors, cells, i, cell, expected
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// All colored clues with the given number of colors that fit on a line of
+/// the given length, as numbers and palette indices.
+pub fn colored_clues(len: usize, palette: usize) -> Vec<(Vec<usize>, Vec<usize>)> {
+    let mut clues = vec![(vec![], vec![])];
+    let mut i = 0;
+    while i < clues.len() {
+        let (numbers, colors): (Vec<usize>, Vec<usize>) = clues[i].clone();
+        let used: usize = numbers.iter().sum::<usize>()
+            + colors.windows(2).filter(|pair| pair[0] == pair[1]).count();
+        for color in 0..palette {
+            let gap = (colors.last() == Some(&color)) as usize;
+            for number in 1..=len {
+                if used + gap + number <= len {
+                    let mut numbers = numbers.clone();
+                    let mut colors = colors.clone();
+                    numbers.push(number);
+                    colors.push(color);
+                    clues.push((numbers, colors));
+                }
+            }
+        }
+        i += 1;
+    }
+    clues
+}
+
+/// The clue of a solved line.
+pub fn runs(solution: &[bool]) -> Vec<usize> {
+    solution
+        .split(|filled| !filled)
+        .map(|run| run.len())
+        .filter(|len| *len > 0)
+        .collect()
+}
+
+/// All clues that fit on a line of the given length.
+pub fn clues(len: usize) -> Vec<Vec<usize>> {
+    let mut clues = vec![vec![]];
+    let mut i = 0;
+    while i < clues.len() {
+        let clue = clues[i].clone();
+        let used: usize = clue.iter().map(|n| n + 1).sum();
+        for number in 1..=len {
+            if used + number <= len {
+                let mut longer = clue.clone();
+                longer.push(number);
+                clues.push(longer);
+            }
+        }
+        i += 1;
+    }
+    clues
+}
+
+/// All lines of the given length made up of undecided, filled and crossed
+/// cells.
+pub fn lines(len: usize) -> Vec<Vec<Cell>> {
+    lines_of(len, &[Cell::Undecided, Cell::Filled, Cell::Crossed])
+}
+
+/// All lines of the given length made up of the given kinds of cells.
+pub fn lines_of(len: usize, cells: &[Cell]) -> Vec<Vec<Cell>> {
+    let mut lines = vec![vec![]];
+    for _ in 0..len {
+        lines = lines
+            .into_iter()
+            .flat_map(|line: Vec<Cell>| {
+                cells
+                    .iter()
+                    .map(move |cell| {
+                        let mut line = line.clone();
+                        line.push(*cell);
+                        line
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+    lines
+}
+
+/// A small deterministic xorshift generator for randomized tests.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// A random solved line of the given length, along with its clue.
+    pub fn solution(&mut self, len: usize) -> (Vec<usize>, Vec<bool>) {
+        let mut clue = vec![];
+        let mut solution = vec![];
+        while solution.len() < len {
+            if self.below(2) == 0 {
+                solution.push(false);
+                continue;
+            }
+            let number = 1 + self.below(len - solution.len());
+            solution.resize(solution.len() + number, true);
+            clue.push(number);
+            if solution.len() < len {
+                solution.push(false);
+            }
+        }
+        (clue, solution)
+    }
+
+    /// Reveals a random subset of the cells of a solution.
+    pub fn reveal(&mut self, solution: &[bool]) -> Vec<Cell> {
+        let density = 1 + self.below(4);
+        solution
+            .iter()
+            .map(|filled| match (self.below(5) < density, filled) {
+                (false, _) => Cell::Undecided,
+                (true, true) => Cell::Filled,
+                (true, false) => Cell::Crossed,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use solver::Pass;
+
+    #[test]
+    fn placements_of_clue() {
+        let undecided = vec![Cell::Undecided; 4];
+        assert_eq!(
+            placements(&[1, 1], &undecided),
+            vec![
+                vec![true, false, true, false],
+                vec![true, false, false, true],
+                vec![false, true, false, true],
+            ]
+        );
+        assert_eq!(placements(&[], &undecided), vec![vec![false; 4]]);
+        assert_eq!(placements(&[5], &undecided), Vec::<Vec<bool>>::new());
+
+        let cells = vec![
+            Cell::Undecided,
+            Cell::Filled,
+            Cell::Undecided,
+            Cell::Crossed,
+        ];
+        assert_eq!(
+            placements(&[2], &cells),
+            vec![
+                vec![true, true, false, false],
+                vec![false, true, true, false]
+            ]
+        );
+    }
+
+    #[test]
+    fn exhaustive_small_lines() {
+        for pass in Pass::all() {
+            for len in 1..=7 {
+                for clue in clues(len) {
+                    for cells in lines(len) {
+                        check_pass(&pass, &clue, &cells).unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn contradictory_lines() {
+        let cells = [
+            Cell::Undecided,
+            Cell::Filled,
+            Cell::Crossed,
+            Cell::Impossible,
+        ];
+        for pass in Pass::all() {
+            for len in 1..=5 {
+                for clue in clues(len) {
+                    for cells in lines_of(len, &cells) {
+                        pass.run(&clue, &grid(&cells).horz_mut(0));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exhaustive_colored_lines() {
+        let palette = 2;
+        let cells: Vec<u8> = (0..1 << (palette + 1)).collect();
+        for pass in Pass::all() {
+            let exact = pass.name() == "discrete";
+            for len in 1..=4 {
+                for (clue, colors) in colored_clues(len, palette) {
+                    for line in lines_of_masks(len, &cells) {
+                        check_colored_pass(&pass, &clue, &colors, palette, &line, exact).unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    fn lines_of_masks(len: usize, cells: &[u8]) -> Vec<Vec<u8>> {
+        (0..len).fold(vec![vec![]], |lines, _| {
+            lines
+                .into_iter()
+                .flat_map(|line: Vec<u8>| {
+                    cells.iter().map(move |cell| {
+                        let mut line = line.clone();
+                        line.push(*cell);
+                        line
+                    })
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn masked_like_crossed() {
+        for pass in Pass::all() {
+            for len in 1..=6 {
+                for clue in clues(len) {
+                    for cells in lines(len) {
+                        let masked: Vec<Cell> = cells
+                            .iter()
+                            .map(|cell| match cell {
+                                Cell::Crossed => Cell::Masked,
+                                cell => *cell,
+                            })
+                            .collect();
+                        assert_eq!(
+                            format!("{:?}", pass.run(&clue, &grid(&cells).horz_mut(0))),
+                            format!("{:?}", pass.run(&clue, &grid(&masked).horz_mut(0)))
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_large_lines() {
+        let mut rng = Rng::new(0x6e6f6e6f);
+        for pass in Pass::all() {
+            for _ in 0..2000 {
+                let len = 8 + rng.below(18);
+                let (clue, solution) = rng.solution(len);
+                let cells = rng.reveal(&solution);
+                check_pass(&pass, &clue, &cells).unwrap();
+            }
+        }
+    }
+}
@@ -1,31 +1,85 @@
 use pest::iterators::Pair;
+use pest::Parser;
 use std::borrow::Cow;
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(Parser)]
 #[grammar = "nono.pest"]
 pub struct NonoParser;
 
+/// The numbers of a clue along with the color of each.
+///
+/// Numbers without a color letter in the one-line format get the default
+/// color `#`. Puzzles where every number has the default color are
+/// monochrome.
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Clue(pub Vec<usize>);
+pub struct Clue {
+    pub numbers: Vec<usize>,
+    /// The color of each number, as many as there are numbers.
+    pub colors: Vec<char>,
+}
+
+/// The default color, used for numbers that are not given a color.
+pub const DEFAULT_COLOR: char = '#';
+
+impl Clue {
+    pub fn is_colored(&self) -> bool {
+        self.colors.iter().any(|color| *color != DEFAULT_COLOR)
+    }
+
+    /// Returns whether any number is zero. A zero is not a block, so puzzles
+    /// holding one are rejected rather than read one way or another.
+    pub fn has_zero(&self) -> bool {
+        self.numbers.contains(&0)
+    }
+}
+
+impl From<Vec<usize>> for Clue {
+    fn from(numbers: Vec<usize>) -> Self {
+        let colors = vec![DEFAULT_COLOR; numbers.len()];
+        Clue { numbers, colors }
+    }
+}
 
 impl<'a> From<Pair<'a, Rule>> for Clue {
     fn from(pair: Pair<Rule>) -> Self {
         assert_eq!(pair.as_rule(), Rule::clue);
-        Clue(
-            pair.into_inner()
-                .map(|n| usize::from_str_radix(n.as_str(), 10).unwrap())
-                .collect::<Vec<_>>(),
-        )
+        let mut numbers = vec![];
+        let mut colors = vec![];
+        for block in pair.into_inner() {
+            let mut inner = block.into_inner();
+            // numbers too large for a usize cannot fit in any line, and
+            // are rejected along with other oversized clues
+            let number = inner.next().unwrap().as_str();
+            numbers.push(number.parse::<usize>().unwrap_or(usize::MAX));
+            colors.push(
+                inner
+                    .next()
+                    .and_then(|color| color.as_str().chars().next())
+                    .unwrap_or(DEFAULT_COLOR),
+            );
+        }
+        Clue { numbers, colors }
     }
 }
 
+fn write_block(f: &mut fmt::Formatter, number: usize, color: char) -> fmt::Result {
+    write!(f, "{}", number)?;
+    if color != DEFAULT_COLOR {
+        write!(f, "{}", color)?;
+    }
+    Ok(())
+}
+
 impl fmt::Display for Clue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if let Some((first, rest)) = self.0.split_first() {
-            write!(f, "{}", first)?;
-            for number in rest {
-                write!(f, ",{}", number)?;
+        let mut blocks = self.numbers.iter().zip(&self.colors);
+        if let Some((first, color)) = blocks.next() {
+            write_block(f, *first, *color)?;
+            for (number, color) in blocks {
+                write!(f, ",")?;
+                write_block(f, *number, *color)?;
             }
         }
         Ok(())
@@ -53,22 +107,32 @@ impl fmt::Display for ClueList {
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Cell {
     Filled,
     Crossed,
     Undecided,
     Impossible,
+    /// Filled with a color other than the default one.
+    Colored(char),
+    /// Filled with a color that is not yet known.
+    Unresolved,
+    /// Outside the board, and crossed for good.
+    Masked,
 }
 
 impl<'a> From<Pair<'a, Rule>> for Cell {
     fn from(pair: Pair<Rule>) -> Self {
         assert_eq!(pair.as_rule(), Rule::cell);
-        match pair.into_inner().next().unwrap().as_rule() {
+        let inner = pair.into_inner().next().unwrap();
+        match inner.as_rule() {
             Rule::filled => Cell::Filled,
             Rule::crossed => Cell::Crossed,
             Rule::undecided => Cell::Undecided,
             Rule::impossible => Cell::Impossible,
+            Rule::unresolved => Cell::Unresolved,
+            Rule::masked => Cell::Masked,
+            Rule::color => Cell::Colored(inner.as_str().chars().next().unwrap()),
             _ => unreachable!(),
         }
     }
@@ -81,6 +145,9 @@ impl fmt::Display for Cell {
             Cell::Crossed => write!(f, "x"),
             Cell::Undecided => write!(f, "."),
             Cell::Impossible => write!(f, "!"),
+            Cell::Colored(color) => write!(f, "{}", color),
+            Cell::Unresolved => write!(f, "?"),
+            Cell::Masked => write!(f, "_"),
         }
     }
 }
@@ -132,7 +199,7 @@ pub struct Puzzle<'a> {
     pub grid: Option<Grid>,
 }
 
-impl<'a> From<Pair<'a, Rule>> for Puzzle<'a> {
+impl<'a, 'b> From<Pair<'a, Rule>> for Puzzle<'b> {
     fn from(pair: Pair<Rule>) -> Self {
         assert_eq!(pair.as_rule(), Rule::puzzle);
         let mut pairs = pair.into_inner();
@@ -147,6 +214,17 @@ impl<'a> From<Pair<'a, Rule>> for Puzzle<'a> {
     }
 }
 
+impl<'a> FromStr for Puzzle<'a> {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NonoParser::parse(Rule::puzzle, s)
+            .map_err(|e| format!("{}", e))?
+            .next()
+            .map(Puzzle::from)
+            .ok_or_else(|| "no puzzle found".to_string())
+    }
+}
+
 impl<'a> fmt::Display for Puzzle<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(grid) = &self.grid {
@@ -157,12 +235,62 @@ impl<'a> fmt::Display for Puzzle<'a> {
     }
 }
 
+/// A triddler: clues for the rows, rising lines and falling lines of a
+/// triangle of triangular cells, and optionally its cells row by row.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Triddler<'a> {
+    pub horz_clues: Cow<'a, ClueList>,
+    pub rising_clues: Cow<'a, ClueList>,
+    pub falling_clues: Cow<'a, ClueList>,
+    pub grid: Option<Grid>,
+}
+
+impl<'a, 'b> From<Pair<'a, Rule>> for Triddler<'b> {
+    fn from(pair: Pair<Rule>) -> Self {
+        assert_eq!(pair.as_rule(), Rule::triddler);
+        let mut pairs = pair.into_inner();
+        let horz_clues = Cow::Owned(pairs.next().map(ClueList::from).unwrap());
+        let rising_clues = Cow::Owned(pairs.next().map(ClueList::from).unwrap());
+        let falling_clues = Cow::Owned(pairs.next().map(ClueList::from).unwrap());
+        let grid = pairs.next().map(Grid::from);
+        Triddler {
+            horz_clues,
+            rising_clues,
+            falling_clues,
+            grid,
+        }
+    }
+}
+
+impl<'a> FromStr for Triddler<'a> {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NonoParser::parse(Rule::triddler, s)
+            .map_err(|e| format!("{}", e))?
+            .next()
+            .map(Triddler::from)
+            .ok_or_else(|| "no triddler found".to_string())
+    }
+}
+
+impl<'a> fmt::Display for Triddler<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<{}|{}|{}",
+            self.horz_clues, self.rising_clues, self.falling_clues
+        )?;
+        if let Some(grid) = &self.grid {
+            write!(f, "|{}", grid)?;
+        }
+        write!(f, ">")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use pest::Parser;
-
     fn test_roundtrip<T, F>(f: F, orig: T)
     where
         F: Fn(&str) -> Vec<T>,
@@ -180,9 +308,16 @@ mod tests {
                 .map(Clue::from)
                 .collect()
         }
-        test_roundtrip(deser, Clue(vec![]));
-        test_roundtrip(deser, Clue(vec![10]));
-        test_roundtrip(deser, Clue(vec![1, 3, 5]));
+        test_roundtrip(deser, Clue::from(vec![]));
+        test_roundtrip(deser, Clue::from(vec![10]));
+        test_roundtrip(deser, Clue::from(vec![1, 3, 5]));
+        test_roundtrip(
+            deser,
+            Clue {
+                numbers: vec![2, 1, 12],
+                colors: vec!['r', '#', 'b'],
+            },
+        );
     }
 
     #[test]
@@ -193,17 +328,34 @@ mod tests {
                 .map(ClueList::from)
                 .collect()
         }
-        test_roundtrip(deser, ClueList(vec![Clue(vec![1])]));
-        test_roundtrip(deser, ClueList(vec![Clue(vec![1]), Clue(vec![2])]));
-        test_roundtrip(deser, ClueList(vec![Clue(vec![]), Clue(vec![2])]));
-        test_roundtrip(deser, ClueList(vec![Clue(vec![1]), Clue(vec![])]));
+        test_roundtrip(deser, ClueList(vec![Clue::from(vec![1])]));
+        test_roundtrip(
+            deser,
+            ClueList(vec![Clue::from(vec![1]), Clue::from(vec![2])]),
+        );
+        test_roundtrip(
+            deser,
+            ClueList(vec![Clue::from(vec![]), Clue::from(vec![2])]),
+        );
+        test_roundtrip(
+            deser,
+            ClueList(vec![Clue::from(vec![1]), Clue::from(vec![])]),
+        );
         test_roundtrip(
             deser,
-            ClueList(vec![Clue(vec![1]), Clue(vec![2]), Clue(vec![3])]),
+            ClueList(vec![
+                Clue::from(vec![1]),
+                Clue::from(vec![2]),
+                Clue::from(vec![3]),
+            ]),
         );
         test_roundtrip(
             deser,
-            ClueList(vec![Clue(vec![1]), Clue(vec![]), Clue(vec![3])]),
+            ClueList(vec![
+                Clue::from(vec![1]),
+                Clue::from(vec![]),
+                Clue::from(vec![3]),
+            ]),
         );
     }
 
@@ -219,6 +371,9 @@ mod tests {
         test_roundtrip(deser, Cell::Crossed);
         test_roundtrip(deser, Cell::Undecided);
         test_roundtrip(deser, Cell::Impossible);
+        test_roundtrip(deser, Cell::Colored('r'));
+        test_roundtrip(deser, Cell::Unresolved);
+        test_roundtrip(deser, Cell::Masked);
     }
 
     #[test]
@@ -251,6 +406,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn triddler() {
+        let orig = Triddler {
+            horz_clues: Cow::Owned(ClueList(vec![Clue::from(vec![]), Clue::from(vec![1, 1])])),
+            rising_clues: Cow::Owned(ClueList(vec![Clue::from(vec![1]), Clue::from(vec![])])),
+            falling_clues: Cow::Owned(ClueList(vec![Clue::from(vec![1]), Clue::from(vec![])])),
+            grid: Some(Grid(vec![
+                GridLine(vec![Cell::Crossed]),
+                GridLine(vec![Cell::Filled, Cell::Crossed, Cell::Filled]),
+            ])),
+        };
+        let s = format!("{}", orig);
+        assert_eq!(s, "<;1,1|1;|1;|x;#x#>");
+        assert_eq!(s.parse::<Triddler>().unwrap(), orig);
+        assert!("[;1,1|1;|x;#x#]".parse::<Triddler>().is_err());
+    }
+
     #[test]
     fn puzzle() {
         fn test_roundtrip(orig: Puzzle) {
@@ -263,17 +435,111 @@ mod tests {
         }
 
         test_roundtrip(Puzzle {
-            vert_clues: Cow::Owned(ClueList(vec![Clue(vec![]), Clue(vec![1])])),
-            horz_clues: Cow::Owned(ClueList(vec![Clue(vec![1]), Clue(vec![])])),
+            vert_clues: Cow::Owned(ClueList(vec![Clue::from(vec![]), Clue::from(vec![1])])),
+            horz_clues: Cow::Owned(ClueList(vec![Clue::from(vec![1]), Clue::from(vec![])])),
             grid: None,
         });
         test_roundtrip(Puzzle {
-            vert_clues: Cow::Owned(ClueList(vec![Clue(vec![]), Clue(vec![1])])),
-            horz_clues: Cow::Owned(ClueList(vec![Clue(vec![1]), Clue(vec![])])),
+            vert_clues: Cow::Owned(ClueList(vec![Clue::from(vec![]), Clue::from(vec![1])])),
+            horz_clues: Cow::Owned(ClueList(vec![Clue::from(vec![1]), Clue::from(vec![])])),
             grid: Some(Grid(vec![
                 GridLine(vec![Cell::Undecided, Cell::Filled]),
                 GridLine(vec![Cell::Crossed, Cell::Impossible]),
             ])),
         });
     }
+
+    mod generated {
+        use super::super::*;
+
+        use proptest::collection::vec;
+        use proptest::prelude::*;
+
+        fn arb_color() -> impl Strategy<Value = char> {
+            prop_oneof![
+                Just(DEFAULT_COLOR),
+                proptest::char::range('a', 'w'),
+                Just('y'),
+                Just('z'),
+            ]
+        }
+
+        fn arb_clue() -> impl Strategy<Value = Clue> {
+            vec((0usize..200, arb_color()), 0..6).prop_map(|blocks| {
+                let (numbers, colors) = blocks.into_iter().unzip();
+                Clue { numbers, colors }
+            })
+        }
+
+        fn arb_clue_list() -> impl Strategy<Value = ClueList> {
+            vec(arb_clue(), 1..8).prop_map(ClueList)
+        }
+
+        fn arb_cell() -> impl Strategy<Value = Cell> {
+            prop_oneof![
+                Just(Cell::Filled),
+                Just(Cell::Crossed),
+                Just(Cell::Undecided),
+                Just(Cell::Impossible),
+                Just(Cell::Unresolved),
+                Just(Cell::Masked),
+                arb_color()
+                    .prop_filter("not a color letter", |color| *color != DEFAULT_COLOR)
+                    .prop_map(Cell::Colored),
+            ]
+        }
+
+        fn arb_grid() -> impl Strategy<Value = Grid> {
+            vec(vec(arb_cell(), 1..8).prop_map(GridLine), 1..8).prop_map(Grid)
+        }
+
+        fn arb_puzzle() -> impl Strategy<Value = Puzzle<'static>> {
+            (
+                arb_clue_list(),
+                arb_clue_list(),
+                proptest::option::of(arb_grid()),
+            )
+                .prop_map(|(vert_clues, horz_clues, grid)| Puzzle {
+                    vert_clues: Cow::Owned(vert_clues),
+                    horz_clues: Cow::Owned(horz_clues),
+                    grid,
+                })
+        }
+
+        fn parse_all<'a, T, F>(rule: Rule, s: &'a str, f: F) -> Vec<T>
+        where
+            F: Fn(Pair<'a, Rule>) -> T,
+        {
+            NonoParser::parse(rule, s)
+                .unwrap_or_else(|e| panic!("{}", e))
+                .map(f)
+                .collect()
+        }
+
+        proptest! {
+            #[test]
+            fn clue(orig in arb_clue()) {
+                let items = parse_all(Rule::clue, &orig.to_string(), Clue::from);
+                prop_assert_eq!(items, vec![orig]);
+            }
+
+            #[test]
+            fn clue_list(orig in arb_clue_list()) {
+                let items = parse_all(Rule::clue_list, &orig.to_string(), ClueList::from);
+                prop_assert_eq!(items, vec![orig]);
+            }
+
+            #[test]
+            fn grid(orig in arb_grid()) {
+                let items = parse_all(Rule::grid, &orig.to_string(), Grid::from);
+                prop_assert_eq!(items, vec![orig]);
+            }
+
+            #[test]
+            fn puzzle(orig in arb_puzzle()) {
+                let items = parse_all(Rule::puzzle, &orig.to_string(), Puzzle::from);
+                prop_assert_eq!(items, vec![orig]);
+            }
+        }
+    }
 }
@@ -0,0 +1,264 @@
+use std::fmt;
+
+use pass::filled_runs;
+use pass::ClueExt;
+use puzzle::Line;
+use puzzle::LineHint;
+use puzzle::LineMut;
+use puzzle::LinePass;
+
+/// Cells filled in every placement that only one number of the clue covers.
+///
+/// The number at `number_index` owns the cells, so its block lies within
+/// `range_start..range_end`. Cells from `turf_start` to `turf_end` outside of
+/// that range are out of reach of every number, the cells that every
+/// placement of the block covers are filled, and the block is capped with
+/// crosses when its placement is fixed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockOwnership {
+    number_index: usize,
+    number: usize,
+    turf_start: usize,
+    range_start: usize,
+    range_end: usize,
+    turf_end: usize,
+}
+
+impl BlockOwnership {
+    fn is_fixed(&self) -> bool {
+        self.range_end - self.range_start == self.number
+    }
+}
+
+/// Explains the hint by naming the owner, e.g. "this block is the 3rd
+/// number, 4".
+impl fmt::Display for BlockOwnership {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ordinal = self.number_index + 1;
+        let suffix = match (ordinal % 10, ordinal % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
+        };
+        write!(
+            f,
+            "this block is the {}{} number, {}",
+            ordinal, suffix, self.number
+        )
+    }
+}
+
+impl LineHint for BlockOwnership {
+    fn check(&self, line: &dyn Line) -> bool {
+        line.range_contains_uncrossed(self.turf_start..self.range_start)
+            || line.range_contains_unfilled(
+                self.range_end - self.number..self.range_start + self.number,
+            )
+            || line.range_contains_uncrossed(self.range_end..self.turf_end)
+            || (self.is_fixed()
+                && ((self.range_start > 0 && !line.is_crossed(self.range_start - 1))
+                    || (self.range_end < line.len() && !line.is_crossed(self.range_end))))
+    }
+    fn apply(&self, line: &mut dyn LineMut) {
+        line.cross_range(self.turf_start..self.range_start);
+        line.fill_range(self.range_end - self.number..self.range_start + self.number);
+        line.cross_range(self.range_end..self.turf_end);
+        if self.is_fixed() {
+            if self.range_start > 0 {
+                line.cross(self.range_start - 1);
+            }
+            if self.range_end < line.len() {
+                line.cross(self.range_end);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BlockOwnershipPass;
+
+impl LinePass for BlockOwnershipPass {
+    type Hint = BlockOwnership;
+    fn run(&self, clue: &[usize], line: &dyn Line) -> Vec<Box<Self::Hint>> {
+        let mut hints = vec![];
+
+        let len = line.len();
+        let (range_starts, mut range_ends) = match (clue.range_starts(line), clue.range_ends(line))
+        {
+            (Some(range_starts), Some(range_ends)) => (range_starts, range_ends),
+            _ => return hints,
+        };
+        range_ends.reverse();
+
+        // the cells owned by each number, as the outermost ones
+        let mut owned: Vec<Option<(usize, usize)>> = vec![None; clue.len()];
+        for run in filled_runs(clue, line) {
+            let mut owners = run.numbers().ones();
+            if let (Some(j), None) = (owners.next(), owners.next()) {
+                owned[j] = match owned[j] {
+                    Some((found_start, _)) => Some((found_start, run.end())),
+                    None => Some((run.start(), run.end())),
+                };
+            }
+        }
+
+        for (j, found) in owned.into_iter().enumerate() {
+            let (found_start, found_end) = match found {
+                Some(found) => found,
+                None => continue,
+            };
+            let number = clue[j];
+            if found_end - found_start > number
+                || (found_start..found_end).any(|x| line.is_crossed(x))
+            {
+                continue;
+            }
+
+            // the block cannot extend past the crosses around the owned cells
+            let space_start = (0..found_start)
+                .rev()
+                .find(|x| line.is_crossed(*x))
+                .map_or(0, |x| x + 1);
+            let space_end = (found_end..len)
+                .find(|x| line.is_crossed(*x))
+                .unwrap_or(len);
+            let range_start = range_starts[j]
+                .max(found_end.saturating_sub(number))
+                .max(space_start);
+            let range_end = range_ends[j].min(found_start + number).min(space_end);
+            if range_start + number > range_end {
+                continue;
+            }
+
+            let turf_start = if j > 0 { range_ends[j - 1] } else { 0 };
+            let turf_end = range_starts.get(j + 1).cloned().unwrap_or(len);
+            let block_ownership = BlockOwnership {
+                number_index: j,
+                number,
+                turf_start: turf_start.min(range_start),
+                range_start,
+                range_end,
+                turf_end: turf_end.max(range_end),
+            };
+            if block_ownership.check(line) {
+                hints.push(Box::new(block_ownership));
+            }
+        }
+        hints
+    }
+    /// Monochrome only: owners are told apart by the crossed cells between
+    /// blocks, which blocks of different colors do not need.
+    fn run_colored(
+        &self,
+        _clue: &[usize],
+        _colors: &[usize],
+        _line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>> {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use oracle::hints;
+
+    #[test]
+    fn bounds() {
+        assert_eq!(
+            hints(&BlockOwnershipPass, &[4], "x.....#..."),
+            vec![BlockOwnership {
+                number_index: 0,
+                number: 4,
+                turf_start: 0,
+                range_start: 3,
+                range_end: 10,
+                turf_end: 10,
+            }]
+        );
+        assert_eq!(
+            hints(&BlockOwnershipPass, &[2, 3], "..#x......"),
+            vec![BlockOwnership {
+                number_index: 0,
+                number: 2,
+                turf_start: 0,
+                range_start: 1,
+                range_end: 3,
+                turf_end: 4,
+            }]
+        );
+    }
+
+    #[test]
+    fn extension() {
+        assert_eq!(
+            hints(&BlockOwnershipPass, &[4], "..#.#....."),
+            vec![BlockOwnership {
+                number_index: 0,
+                number: 4,
+                turf_start: 0,
+                range_start: 1,
+                range_end: 6,
+                turf_end: 10,
+            }]
+        );
+        assert_eq!(
+            hints(&BlockOwnershipPass, &[1, 1], "..#..#.."),
+            vec![
+                BlockOwnership {
+                    number_index: 0,
+                    number: 1,
+                    turf_start: 0,
+                    range_start: 2,
+                    range_end: 3,
+                    turf_end: 3,
+                },
+                BlockOwnership {
+                    number_index: 1,
+                    number: 1,
+                    turf_start: 5,
+                    range_start: 5,
+                    range_end: 6,
+                    turf_end: 8,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn owners() {
+        // the last cell can only be covered by the 3rd number
+        let found = hints(&BlockOwnershipPass, &[1, 1, 4], "....x....#");
+        assert_eq!(
+            found
+                .iter()
+                .map(|hint| hint.to_string())
+                .collect::<Vec<_>>(),
+            vec!["this block is the 3rd number, 4"]
+        );
+        let ordinals: Vec<String> = [0, 1, 10, 11, 20, 21, 22, 111, 112]
+            .iter()
+            .map(|number_index| {
+                BlockOwnership {
+                    number_index: *number_index,
+                    number: 1,
+                    turf_start: 0,
+                    range_start: 0,
+                    range_end: 1,
+                    turf_end: 1,
+                }
+                .to_string()
+            })
+            .collect();
+        assert_eq!(
+            ordinals,
+            ["1st", "2nd", "11th", "12th", "21st", "22nd", "23rd", "112th", "113th"]
+                .iter()
+                .map(|ordinal| format!("this block is the {} number, 1", ordinal))
+                .collect::<Vec<_>>()
+        );
+    }
+}
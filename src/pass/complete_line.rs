@@ -0,0 +1,63 @@
+use accounting;
+use puzzle::Line;
+use puzzle::LineHint;
+use puzzle::LineMut;
+use puzzle::LinePass;
+
+/// Every number of the clue is placed, so the cells that are left over are
+/// all crossed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompleteLine;
+
+impl LineHint for CompleteLine {
+    fn check(&self, line: &dyn Line) -> bool {
+        (0..line.len()).any(|i| !line.is_filled(i) && !line.is_crossed(i))
+    }
+    fn apply(&self, line: &mut dyn LineMut) {
+        for i in 0..line.len() {
+            if !line.is_filled(i) {
+                line.cross(i);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CompleteLinePass;
+
+impl LinePass for CompleteLinePass {
+    type Hint = CompleteLine;
+    fn run(&self, clue: &[usize], line: &dyn Line) -> Vec<Box<Self::Hint>> {
+        self.run_colored(clue, &[], line)
+    }
+    fn run_colored(
+        &self,
+        clue: &[usize],
+        colors: &[usize],
+        line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>> {
+        let satisfied = accounting::satisfied(clue, colors, line);
+        if satisfied.count_ones(..) == clue.len() && CompleteLine.check(line) {
+            vec![Box::new(CompleteLine)]
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use oracle::hints;
+
+    #[test]
+    fn complete_line() {
+        let run = |clue: &[usize], cells: &str| hints(&CompleteLinePass, clue, cells).len();
+        assert_eq!(run(&[2, 1], ".##..#."), 1);
+        assert_eq!(run(&[2, 1], "##x.x#"), 1);
+        assert_eq!(run(&[2, 1], "##x..##"), 0);
+        assert_eq!(run(&[2, 1], "##x#x#x"), 0);
+        assert_eq!(run(&[], "..."), 1);
+    }
+}
@@ -5,83 +5,95 @@ use puzzle::LineHint;
 use puzzle::LineMut;
 use puzzle::LinePass;
 
+/// Leftmost starts and rightmost ends of each number in a clue.
+///
+/// Both are `None` if the numbers cannot be fitted into the line at all.
 pub trait ClueExt {
-    fn range_starts(&self, line: &Line) -> Vec<usize>;
-    fn range_ends(&self, line: &Line) -> Vec<usize>;
+    fn range_starts(&self, line: &dyn Line) -> Option<Vec<usize>>;
+    fn range_ends(&self, line: &dyn Line) -> Option<Vec<usize>>;
 }
 
-impl<'a> ClueExt for &'a [usize] {
-    fn range_starts(&self, line: &Line) -> Vec<usize> {
+impl ClueExt for &[usize] {
+    fn range_starts(&self, line: &dyn Line) -> Option<Vec<usize>> {
         let mut range_starts = Vec::with_capacity(self.len());
         let mut start = 0;
         for number in self.iter() {
+            if start + number > line.len() {
+                return None;
+            }
             start = line.bump_start(start, *number);
             range_starts.push(start);
             start += number + 1;
         }
         //println!("  starts {:?}", range_starts);
-        range_starts
+        if start > line.len() + 1 {
+            return None;
+        }
+        Some(range_starts)
     }
 
-    fn range_ends(&self, line: &Line) -> Vec<usize> {
+    fn range_ends(&self, line: &dyn Line) -> Option<Vec<usize>> {
         let mut range_ends = Vec::with_capacity(self.len());
         let mut last = line.len() as isize - 1;
         for number in self.iter().rev() {
+            if last + 1 < *number as isize {
+                return None;
+            }
             last = line.bump_last(last as usize, *number);
             range_ends.push(last as usize);
             last -= *number as isize + 2;
         }
         //println!("  ends {:?}", range_ends);
-        range_ends
+        Some(range_ends)
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Unreachable {
     reachable_start: usize,
     reachable_end: usize,
 }
 
 impl LineHint for Unreachable {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         let len = line.len();
         line.range_contains_uncrossed(0..self.reachable_start)
             || line.range_contains_uncrossed(self.reachable_end..len)
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         let len = line.len();
         line.cross_range(0..self.reachable_start);
         line.cross_range(self.reachable_end..len);
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Kernel {
     kernel_start: usize,
     kernel_end: usize,
 }
 
 impl LineHint for Kernel {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         line.range_contains_unfilled(self.kernel_start..self.kernel_end)
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         line.fill_range(self.kernel_start..self.kernel_end);
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Termination {
     range_start: usize,
     range_end: usize,
 }
 
 impl LineHint for Termination {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         (self.range_start > 0 && !line.is_crossed(self.range_start - 1))
             || (self.range_end < line.len() && !line.is_crossed(self.range_end))
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         if self.range_start > 0 {
             line.cross(self.range_start - 1);
         }
@@ -91,7 +103,7 @@ impl LineHint for Termination {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct TurfNearSingleton {
     found_start: usize,
     kernel_start: usize,
@@ -100,17 +112,17 @@ pub struct TurfNearSingleton {
 }
 
 impl LineHint for TurfNearSingleton {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         line.range_contains_unfilled(self.found_start..self.kernel_start)
             || line.range_contains_uncrossed(self.reachable_end..self.turf_end)
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         line.fill_range(self.found_start..self.kernel_start);
         line.cross_range(self.reachable_end..self.turf_end);
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct TurfFarSingleton {
     turf_start: usize,
     reachable_start: usize,
@@ -119,17 +131,17 @@ pub struct TurfFarSingleton {
 }
 
 impl LineHint for TurfFarSingleton {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         line.range_contains_uncrossed(self.turf_start..self.reachable_start)
             || line.range_contains_unfilled(self.kernel_end..self.found_end)
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         line.cross_range(self.turf_start..self.reachable_start);
         line.fill_range(self.kernel_end..self.found_end);
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct TurfPair {
     turf_start: usize,
     reachable_start: usize,
@@ -140,19 +152,19 @@ pub struct TurfPair {
 }
 
 impl LineHint for TurfPair {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         line.range_contains_uncrossed(self.turf_start..self.reachable_start)
             || line.range_contains_unfilled(self.found_start + 1..self.found_end - 1)
             || line.range_contains_uncrossed(self.reachable_end..self.turf_end)
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         line.cross_range(self.turf_start..self.reachable_start);
         line.fill_range(self.found_start + 1..self.found_end - 1);
         line.cross_range(self.reachable_end..self.turf_end);
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct TurfSingleton {
     turf_start: usize,
     reachable_start: usize,
@@ -161,17 +173,17 @@ pub struct TurfSingleton {
 }
 
 impl LineHint for TurfSingleton {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         line.range_contains_uncrossed(self.turf_start..self.reachable_start)
             || line.range_contains_uncrossed(self.reachable_end..self.turf_end)
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         line.cross_range(self.turf_start..self.reachable_start);
         line.cross_range(self.reachable_end..self.turf_end);
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ContinuousRangeHint {
     Unreachable(Unreachable),
     Kernel(Kernel),
@@ -183,7 +195,7 @@ pub enum ContinuousRangeHint {
 }
 
 impl LineHint for ContinuousRangeHint {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         match self {
             ContinuousRangeHint::Unreachable(inner) => inner.check(line),
             ContinuousRangeHint::Kernel(inner) => inner.check(line),
@@ -194,7 +206,7 @@ impl LineHint for ContinuousRangeHint {
             ContinuousRangeHint::TurfSingleton(inner) => inner.check(line),
         }
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         match self {
             ContinuousRangeHint::Unreachable(inner) => inner.apply(line),
             ContinuousRangeHint::Kernel(inner) => inner.apply(line),
@@ -212,23 +224,34 @@ pub struct ContinuousRangePass;
 
 impl LinePass for ContinuousRangePass {
     type Hint = ContinuousRangeHint;
-    fn run(&self, clue: &[usize], line: &Line) -> Vec<Box<Self::Hint>> {
+    fn run(&self, clue: &[usize], line: &dyn Line) -> Vec<Box<Self::Hint>> {
         let mut hints: Vec<Box<Self::Hint>> = vec![];
         //println!("CLUE  {:?}", clue);
 
-        let range_starts = clue.range_starts(line);
-        let range_ends = clue.range_ends(line);
+        let len = line.len();
+        let (range_starts, range_ends) = match (clue.range_starts(line), clue.range_ends(line)) {
+            (Some(range_starts), Some(range_ends)) => (range_starts, range_ends),
+            _ => return hints,
+        };
+        // a number that does not fit between its start and end means the
+        // line contradicts the clue
+        let fits = clue
+            .iter()
+            .zip(range_starts.iter().zip(range_ends.iter().rev()))
+            .all(|(number, (start, end))| start + number <= *end);
+        if !fits {
+            return hints;
+        }
 
         // unreachable cells
         let unreachable = Unreachable {
-            reachable_start: range_starts[0],
-            reachable_end: range_ends[0],
+            reachable_start: range_starts.first().cloned().unwrap_or(len),
+            reachable_end: range_ends.first().cloned().unwrap_or(len),
         };
         if unreachable.check(line) {
             hints.push(Box::new(ContinuousRangeHint::Unreachable(unreachable)));
         }
 
-        let len = line.len();
         let turf_ends = range_starts
             .iter()
             .skip(1)
@@ -335,4 +358,14 @@ impl LinePass for ContinuousRangePass {
         }
         hints
     }
+    /// Monochrome only: the ranges assume a crossed cell between every two
+    /// numbers, which blocks of different colors do not need.
+    fn run_colored(
+        &self,
+        _clue: &[usize],
+        _colors: &[usize],
+        _line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>> {
+        vec![]
+    }
 }
@@ -1,20 +1,51 @@
+use std::fmt;
+
 use puzzle::Line;
 use puzzle::LineHint;
 use puzzle::LineMut;
 use puzzle::LinePass;
 
-#[derive(Debug)]
+#[derive(Clone)]
 pub struct CrowdedClue {
     kernel_start: usize,
     kernel_end: usize,
+    /// Palette index of the color of the kernel, for colored puzzles.
+    color: Option<usize>,
+}
+
+impl fmt::Debug for CrowdedClue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = f.debug_struct("CrowdedClue");
+        s.field("kernel_start", &self.kernel_start);
+        s.field("kernel_end", &self.kernel_end);
+        if let Some(color) = self.color {
+            s.field("color", &color);
+        }
+        s.finish()
+    }
+}
+
+impl CrowdedClue {
+    fn other_colors<'a>(&self, line: &'a dyn Line) -> impl Iterator<Item = usize> + 'a {
+        let color = self.color;
+        (0..line.colors()).filter(move |c| Some(*c) != color)
+    }
 }
 
 impl LineHint for CrowdedClue {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         line.range_contains_unfilled(self.kernel_start..self.kernel_end)
+            || (self.kernel_start..self.kernel_end)
+                .any(|i| self.other_colors(line).any(|c| !line.is_excluded(i, c)))
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         line.fill_range(self.kernel_start..self.kernel_end);
+        let others: Vec<usize> = self.other_colors(line).collect();
+        for i in self.kernel_start..self.kernel_end {
+            for c in &others {
+                line.exclude(i, *c);
+            }
+        }
     }
 }
 
@@ -23,8 +54,11 @@ pub struct CrowdedCluePass;
 
 impl LinePass for CrowdedCluePass {
     type Hint = CrowdedClue;
-    fn run(&self, clue: &[usize], line: &Line) -> Vec<Box<Self::Hint>> {
+    fn run(&self, clue: &[usize], line: &dyn Line) -> Vec<Box<Self::Hint>> {
         let mut hints: Vec<Box<Self::Hint>> = vec![];
+        if clue.is_empty() {
+            return hints;
+        }
         let sum: usize = clue.iter().sum();
         let freedom: usize = line.len() - (sum + clue.len() - 1);
         let mut x0 = 0;
@@ -33,6 +67,7 @@ impl LinePass for CrowdedCluePass {
                 let hint = Box::new(CrowdedClue {
                     kernel_start: x0 + freedom,
                     kernel_end: x0 + number,
+                    color: None,
                 });
                 if hint.check(line) {
                     hints.push(hint);
@@ -42,4 +77,36 @@ impl LinePass for CrowdedCluePass {
         }
         hints
     }
+
+    fn run_colored(
+        &self,
+        clue: &[usize],
+        colors: &[usize],
+        line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>> {
+        let mut hints: Vec<Box<Self::Hint>> = vec![];
+        // neighbouring numbers of different colors may touch
+        let gaps = colors.windows(2).map(|pair| (pair[0] == pair[1]) as usize);
+        let gaps: Vec<usize> = gaps.chain(Some(0)).collect();
+        let needed: usize = clue.iter().sum::<usize>() + gaps.iter().sum::<usize>();
+        let freedom = match line.len().checked_sub(needed) {
+            Some(freedom) => freedom,
+            None => return hints,
+        };
+        let mut x0 = 0;
+        for ((number, color), gap) in clue.iter().zip(colors).zip(gaps) {
+            if *number > freedom {
+                let hint = Box::new(CrowdedClue {
+                    kernel_start: x0 + freedom,
+                    kernel_end: x0 + number,
+                    color: Some(*color),
+                });
+                if hint.check(line) {
+                    hints.push(hint);
+                }
+            }
+            x0 += number + gap;
+        }
+        hints
+    }
 }
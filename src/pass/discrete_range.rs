@@ -1,23 +1,36 @@
 use fixedbitset::FixedBitSet;
-use parser::Cell;
 
 use puzzle::Line;
 use puzzle::LineHint;
 use puzzle::LineMut;
 use puzzle::LinePass;
 
-#[derive(Debug, Eq)]
+#[derive(Clone, Debug, Eq)]
 pub struct FilledRun {
     start: usize,
     end: usize,
     numbers: FixedBitSet,
 }
 
+impl FilledRun {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+    pub fn end(&self) -> usize {
+        self.end
+    }
+    /// The indices of the numbers of the clue whose block covers the run in
+    /// some placement.
+    pub fn numbers(&self) -> &FixedBitSet {
+        &self.numbers
+    }
+}
+
 impl LineHint for FilledRun {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         line.range_contains_unfilled(self.start..self.end)
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         line.fill_range(self.start..self.end)
     }
 }
@@ -31,108 +44,83 @@ impl PartialEq for FilledRun {
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CrossedRun {
     start: usize,
     end: usize,
 }
 
 impl LineHint for CrossedRun {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         line.range_contains_uncrossed(self.start..self.end)
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         line.cross_range(self.start..self.end)
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// A run of cells of a colored line that share the same possibilities.
+///
+/// Colors outside `colors` are ruled out for every cell of the run. If the
+/// cells cannot be background they are filled, and if they can be nothing
+/// but background they are crossed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ColoredRun {
+    start: usize,
+    end: usize,
+    background: bool,
+    colors: FixedBitSet,
+}
+
+impl LineHint for ColoredRun {
+    fn check(&self, line: &dyn Line) -> bool {
+        let must_cross = self.background && self.colors.count_ones(..) == 0;
+        (!self.background && line.range_contains_unfilled(self.start..self.end))
+            || (must_cross && line.range_contains_uncrossed(self.start..self.end))
+            || (self.start..self.end).any(|i| {
+                (0..line.colors()).any(|c| !self.colors.contains(c) && !line.is_excluded(i, c))
+            })
+    }
+    fn apply(&self, line: &mut dyn LineMut) {
+        if !self.background {
+            line.fill_range(self.start..self.end);
+        } else if self.colors.count_ones(..) == 0 {
+            line.cross_range(self.start..self.end);
+        }
+        for i in self.start..self.end {
+            for c in 0..line.colors() {
+                if !self.colors.contains(c) {
+                    line.exclude(i, c);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum DiscreteRangeHint {
     CrossedRun(CrossedRun),
     FilledRun(FilledRun),
+    ColoredRun(ColoredRun),
 }
 
 impl LineHint for DiscreteRangeHint {
-    fn check(&self, line: &Line) -> bool {
+    fn check(&self, line: &dyn Line) -> bool {
         match self {
             DiscreteRangeHint::CrossedRun(inner) => inner.check(line),
             DiscreteRangeHint::FilledRun(inner) => inner.check(line),
+            DiscreteRangeHint::ColoredRun(inner) => inner.check(line),
         }
     }
-    fn apply(&self, line: &mut LineMut) {
+    fn apply(&self, line: &mut dyn LineMut) {
         match self {
             DiscreteRangeHint::CrossedRun(inner) => inner.apply(line),
             DiscreteRangeHint::FilledRun(inner) => inner.apply(line),
+            DiscreteRangeHint::ColoredRun(inner) => inner.apply(line),
         }
     }
 }
 
-#[derive(Clone, Copy)]
-enum State {
-    Empty(usize),
-    Filled(usize, usize),
-    End,
-}
-
-impl State {
-    fn start() -> State {
-        State::Empty(0)
-    }
-    fn cell(self, cell: Cell) -> Self {
-        match (self, cell) {
-            (State::Empty(_), Cell::Crossed) => State::Empty(0),
-            (State::Empty(n), Cell::Undecided) => State::Empty(n + 1),
-            (State::Empty(n), Cell::Filled) => State::Filled(1, n + 1),
-            (State::Filled(m, n), Cell::Undecided) => State::Filled(m + 1, n + 1),
-            (State::Filled(m, n), Cell::Filled) => State::Filled(m + 1, n + 1),
-            (State::Filled(_, _), Cell::Crossed) => State::End,
-            (State::End, _) => State::End,
-            (_, Cell::Impossible) => State::End,
-        }
-    }
-}
-
-struct Iter<'a> {
-    line: &'a Line,
-    number: usize,
-    focus: usize,
-    state: State,
-}
-
-impl<'a> Iter<'a> {
-    fn new(line: &'a Line, number: usize, start: usize) -> Self {
-        Iter {
-            line,
-            number,
-            focus: start,
-            state: State::start(),
-        }
-    }
-}
-
-impl<'a> Iterator for Iter<'a> {
-    type Item = usize;
-    fn next(&mut self) -> Option<Self::Item> {
-        for focus in self.focus..self.line.len() {
-            self.state = match self.state.cell(self.line.get(focus)) {
-                State::Filled(m, _) if m > self.number => State::End,
-                state => state,
-            };
-            let emit = match self.state {
-                State::Filled(_, n) if n >= self.number => true,
-                State::Empty(n) if n >= self.number => true,
-                _ => false,
-            };
-            if emit && (focus + 1 >= self.line.len() || !self.line.is_filled(focus + 1)) {
-                self.focus = focus + 1;
-                return Some(self.focus - self.number);
-            }
-        }
-        self.focus = self.line.len();
-        None
-    }
-}
-
 struct Possibilities {
     filled: FixedBitSet,
     crossed: FixedBitSet,
@@ -155,47 +143,128 @@ impl Possibilities {
         }
     }
 
-    fn positions(&mut self, positions: &[usize], clue: &[usize]) {
-        //println!(" OK {} {:?}", start, &positions);
-        let mut old_end = 0;
-        for ((number_index, number), start) in clue.iter().enumerate().zip(positions) {
-            //println!("  filled {}..{}", old_end, start);
-            for j in old_end..*start {
-                self.filled.set(j, false);
+    /// Finds what the placements of the clue that agree with the line have in
+    /// common, without going through them one by one.
+    ///
+    /// `before[j][i]` tells whether the first `j` numbers can be placed in the
+    /// first `i` cells, and `after[j][i]` whether the numbers from `j` on can
+    /// be placed in the cells from `i` on, with the cells left over empty.
+    fn solve(&mut self, line: &dyn Line, clue: &[usize]) {
+        let len = line.len();
+        let count = clue.len();
+        let can_cross: Vec<bool> = (0..len).map(|i| !line.is_filled(i)).collect();
+        // the number of cells before each cell that cannot be filled
+        let mut blocked = vec![0; len + 1];
+        for i in 0..len {
+            blocked[i + 1] = blocked[i] + line.is_crossed(i) as usize;
+        }
+        let fits = |start: usize, end: usize| blocked[end] == blocked[start];
+
+        let mut before = vec![vec![false; len + 1]; count + 1];
+        before[0][0] = true;
+        for i in 1..=len {
+            before[0][i] = before[0][i - 1] && can_cross[i - 1];
+        }
+        let mut after = vec![vec![false; len + 1]; count + 1];
+        after[count][len] = true;
+        for i in (0..len).rev() {
+            after[count][i] = after[count][i + 1] && can_cross[i];
+        }
+        for j in 1..=count {
+            let number = clue[j - 1];
+            for i in 0..=len {
+                let gap = i > 0 && can_cross[i - 1] && before[j][i - 1];
+                let block = i >= number && fits(i - number, i) && {
+                    let start = i - number;
+                    if j == 1 {
+                        before[0][start]
+                    } else {
+                        start > 0 && can_cross[start - 1] && before[j - 1][start - 1]
+                    }
+                };
+                before[j][i] = gap || block;
+            }
+        }
+        for j in (0..count).rev() {
+            let number = clue[j];
+            for i in (0..=len).rev() {
+                let gap = i < len && can_cross[i] && after[j][i + 1];
+                let end = i + number;
+                let block = end <= len && fits(i, end) && {
+                    if j + 1 == count {
+                        after[count][end]
+                    } else {
+                        end < len && can_cross[end] && after[j + 1][end + 1]
+                    }
+                };
+                after[j][i] = gap || block;
             }
-            //println!("  crossed {}..{}", *start, *start + number);
-            for j in *start..*start + number {
-                self.crossed.set(j, false);
-                self.cell_numbers.put(j * clue.len() + number_index);
+        }
+        if !after[0][0] {
+            return;
+        }
+
+        for (j, number) in clue.iter().enumerate() {
+            for start in 0..(len + 1).saturating_sub(*number) {
+                let end = start + number;
+                let placed = fits(start, end)
+                    && if j == 0 {
+                        before[0][start]
+                    } else {
+                        start > 0 && can_cross[start - 1] && before[j][start - 1]
+                    }
+                    && if j + 1 == count {
+                        after[count][end]
+                    } else {
+                        end < len && can_cross[end] && after[j + 1][end + 1]
+                    };
+                if placed {
+                    for i in start..end {
+                        self.crossed.set(i, false);
+                        self.cell_numbers.put(i * count + j);
+                    }
+                }
             }
-            old_end = *start + number;
         }
-        //println!("  filled {}..{}", old_end, line.len());
-        for j in old_end..self.filled.len() {
-            self.filled.set(j, false);
+        for i in 0..len {
+            if can_cross[i] && (0..=count).any(|j| before[j][i] && after[j][i + 1]) {
+                self.filled.set(i, false);
+            }
         }
     }
 
-    fn solve(
-        &mut self,
-        line: &Line,
-        clue: &[usize],
-        depth: usize,
-        start: usize,
-        positions: &mut Vec<usize>,
-    ) {
-        if let Some(number) = clue.get(depth) {
-            for start in Iter::new(line, *number, start) {
-                positions.push(start);
-                self.solve(line, clue, depth + 1, start + number + 1, positions);
-                positions.pop();
+    fn filled_run(&self, start: usize, end: usize, clue: &[usize]) -> FilledRun {
+        let mut numbers = FixedBitSet::with_capacity(clue.len());
+        for j in 0..clue.len() {
+            if self.cell_numbers.contains(start * clue.len() + j) {
+                numbers.put(j);
             }
-        } else if !line.range_contains_filled(start..line.len()) {
-            self.positions(positions, clue);
+        }
+        FilledRun {
+            start,
+            end,
+            numbers,
         }
     }
 
-    fn hints(&self, line: &Line, clue: &[usize]) -> Vec<Box<DiscreteRangeHint>> {
+    fn filled_runs(&self, clue: &[usize]) -> Vec<FilledRun> {
+        let mut runs = vec![];
+        let mut i = 0;
+        while i < self.filled.len() {
+            if !self.filled.contains(i) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < self.filled.len() && self.filled.contains(i) {
+                i += 1;
+            }
+            runs.push(self.filled_run(start, i, clue));
+        }
+        runs
+    }
+
+    fn hints(&self, line: &dyn Line, clue: &[usize]) -> Vec<DiscreteRangeHint> {
         /*
         println!("filled {:?}", self.filled.ones().collect::<Vec<_>>());
         println!("crossed {:?}", self.crossed.ones().collect::<Vec<_>>());
@@ -205,7 +274,7 @@ impl Possibilities {
         );
         */
 
-        let mut hints: Vec<Box<DiscreteRangeHint>> = vec![];
+        let mut hints: Vec<DiscreteRangeHint> = vec![];
         let mut i = 0;
         while i < self.filled.len() {
             while i < self.filled.len() && !self.filled.contains(i) && !self.crossed.contains(i) {
@@ -219,19 +288,9 @@ impl Possibilities {
                 while i < line.len() && self.filled.contains(i) {
                     i += 1;
                 }
-                let mut numbers = FixedBitSet::with_capacity(clue.len());
-                for j in 0..clue.len() {
-                    if self.cell_numbers.contains(start * clue.len() + j) {
-                        numbers.put(j);
-                    }
-                }
-                let filled_run = FilledRun {
-                    start,
-                    end: i,
-                    numbers,
-                };
+                let filled_run = self.filled_run(start, i, clue);
                 if filled_run.check(line) {
-                    hints.push(Box::new(DiscreteRangeHint::FilledRun(filled_run)));
+                    hints.push(DiscreteRangeHint::FilledRun(filled_run));
                 }
             } else {
                 while i < line.len() && self.crossed.contains(i) {
@@ -239,7 +298,7 @@ impl Possibilities {
                 }
                 let crossed_run = CrossedRun { start, end: i };
                 if crossed_run.check(line) {
-                    hints.push(Box::new(DiscreteRangeHint::CrossedRun(crossed_run)));
+                    hints.push(DiscreteRangeHint::CrossedRun(crossed_run));
                 }
             }
         }
@@ -248,18 +307,171 @@ impl Possibilities {
     }
 }
 
+/// Which placements of the numbers of a colored clue agree with a line.
+///
+/// Placements are walked cell by cell. After `i` cells, `j` numbers have been
+/// placed, and the last cell is either background or the end of number
+/// `j - 1`. Neighbouring numbers only need a gap between them if they have
+/// the same color.
+struct ColoredPlacements<'a> {
+    clue: &'a [usize],
+    colors: &'a [usize],
+    line: &'a dyn Line,
+}
+
+impl<'a> ColoredPlacements<'a> {
+    fn index(&self, i: usize, j: usize, ended: bool) -> usize {
+        ((i * (self.clue.len() + 1)) + j) * 2 + ended as usize
+    }
+
+    fn can_be_background(&self, i: usize) -> bool {
+        !self.line.is_filled(i)
+    }
+
+    /// Whether number `j` can be placed starting at cell `i`, given whether
+    /// the previous number ends right before it.
+    fn fits(&self, i: usize, j: usize, ended: bool) -> bool {
+        let end = i + self.clue[j];
+        let color = self.colors[j];
+        end <= self.line.len()
+            && !(ended && self.colors[j - 1] == color)
+            && (i..end).all(|k| !self.line.is_crossed(k) && !self.line.is_excluded(k, color))
+    }
+
+    /// States from which the rest of the line can be completed.
+    fn completable(&self) -> FixedBitSet {
+        let len = self.line.len();
+        let k = self.clue.len();
+        let mut good = FixedBitSet::with_capacity(self.index(len + 1, 0, false));
+        good.put(self.index(len, k, false));
+        if k > 0 {
+            good.put(self.index(len, k, true));
+        }
+        for i in (0..len).rev() {
+            for j in 0..=k {
+                for &ended in &[false, true] {
+                    if ended && j == 0 {
+                        continue;
+                    }
+                    let background =
+                        self.can_be_background(i) && good.contains(self.index(i + 1, j, false));
+                    let number = j < k
+                        && self.fits(i, j, ended)
+                        && good.contains(self.index(i + self.clue[j], j + 1, true));
+                    if background || number {
+                        good.put(self.index(i, j, ended));
+                    }
+                }
+            }
+        }
+        good
+    }
+
+    /// What each cell can be across all placements, as whether it can be
+    /// background and which colors it can have.
+    fn cells(&self) -> Option<(FixedBitSet, Vec<FixedBitSet>)> {
+        let len = self.line.len();
+        let k = self.clue.len();
+        let good = self.completable();
+        if !good.contains(self.index(0, 0, false)) {
+            return None;
+        }
+        let mut reached = FixedBitSet::with_capacity(good.len());
+        reached.put(self.index(0, 0, false));
+        let mut background = FixedBitSet::with_capacity(len);
+        let mut colors = vec![FixedBitSet::with_capacity(self.line.colors()); len];
+        for i in 0..len {
+            for j in 0..=k {
+                for &ended in &[false, true] {
+                    if !reached.contains(self.index(i, j, ended)) {
+                        continue;
+                    }
+                    let next = self.index(i + 1, j, false);
+                    if self.can_be_background(i) && good.contains(next) {
+                        reached.put(next);
+                        background.put(i);
+                    }
+                    if j < k && self.fits(i, j, ended) {
+                        let next = self.index(i + self.clue[j], j + 1, true);
+                        if good.contains(next) {
+                            reached.put(next);
+                            for cell in &mut colors[i..i + self.clue[j]] {
+                                cell.put(self.colors[j]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Some((background, colors))
+    }
+}
+
+/// The runs of cells that are filled in every placement of the clue that
+/// agrees with the line, including the ones that are filled already.
+///
+/// If no placement agrees with the line, the whole line is one run that no
+/// number covers.
+pub fn filled_runs(clue: &[usize], line: &dyn Line) -> Vec<FilledRun> {
+    let mut possibilities = Possibilities::new(line.len(), clue.len());
+    possibilities.solve(line, clue);
+    possibilities.filled_runs(clue)
+}
+
 #[derive(Debug)]
 pub struct DiscreteRangePass;
 
 impl LinePass for DiscreteRangePass {
     type Hint = DiscreteRangeHint;
 
-    fn run(&self, clue: &[usize], line: &Line) -> Vec<Box<Self::Hint>> {
+    fn run(&self, clue: &[usize], line: &dyn Line) -> Vec<Box<Self::Hint>> {
         let mut possibilities = Possibilities::new(line.len(), clue.len());
 
-        possibilities.solve(line, clue, 0, 0, &mut vec![]);
+        possibilities.solve(line, clue);
 
-        possibilities.hints(line, clue)
+        possibilities
+            .hints(line, clue)
+            .into_iter()
+            .map(Box::new)
+            .collect()
+    }
+
+    fn run_colored(
+        &self,
+        clue: &[usize],
+        colors: &[usize],
+        line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>> {
+        let placements = ColoredPlacements { clue, colors, line };
+        let (background, colors) = match placements.cells() {
+            Some(cells) => cells,
+            // no placement agrees with the line, so every cell is impossible
+            None => (
+                FixedBitSet::with_capacity(line.len()),
+                vec![FixedBitSet::with_capacity(line.colors()); line.len()],
+            ),
+        };
+        let mut hints: Vec<Box<Self::Hint>> = vec![];
+        let mut start = 0;
+        for i in 1..=line.len() {
+            if i < line.len()
+                && background.contains(i) == background.contains(start)
+                && colors[i] == colors[start]
+            {
+                continue;
+            }
+            let colored_run = ColoredRun {
+                start,
+                end: i,
+                background: background.contains(start),
+                colors: colors[start].clone(),
+            };
+            if colored_run.check(line) {
+                hints.push(Box::new(DiscreteRangeHint::ColoredRun(colored_run)));
+            }
+            start = i;
+        }
+        hints
     }
 }
 
@@ -267,10 +479,42 @@ impl LinePass for DiscreteRangePass {
 mod tests {
     use super::*;
 
-    use puzzle::line_grid;
+    use oracle;
+    use oracle::line_grid;
+    use parser::Cell;
     use puzzle::Grid;
     use std::iter::FromIterator;
 
+    #[test]
+    fn exact() {
+        for len in 1..=7 {
+            for clue in oracle::clues(len) {
+                for line in oracle::lines(len) {
+                    let placements = oracle::placements(&clue, &line);
+                    if placements.is_empty() {
+                        continue;
+                    }
+                    let s: String = line.iter().map(|cell| cell.to_string()).collect();
+                    let mut grid = line_grid(&s);
+                    for hint in DiscreteRangePass.run(&clue, &grid.horz_mut(0)) {
+                        hint.apply(&mut grid.horz_mut(0));
+                    }
+                    for i in 0..len {
+                        let expected = match (
+                            placements.iter().all(|p| p[i]),
+                            placements.iter().all(|p| !p[i]),
+                        ) {
+                            (true, _) => Cell::Filled,
+                            (_, true) => Cell::Crossed,
+                            _ => Cell::Undecided,
+                        };
+                        assert_eq!(grid.get(i, 0), expected, "{:?} {}", clue, s);
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn run1() {
         let mut grid = Grid::new(4, 1);
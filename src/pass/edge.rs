@@ -0,0 +1,229 @@
+use std::ops::Range;
+
+use parser::Cell;
+use puzzle::Line;
+use puzzle::LineHint;
+use puzzle::LineMut;
+use puzzle::LinePass;
+
+/// Cells between the border and a cross too close to it for the nearest
+/// number to fit. Like mercury, the block slides away from the border past
+/// the cross, leaving the cells behind it empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mercury {
+    start: usize,
+    end: usize,
+}
+
+impl LineHint for Mercury {
+    fn check(&self, line: &dyn Line) -> bool {
+        line.range_contains_uncrossed(self.start..self.end)
+    }
+    fn apply(&self, line: &mut dyn LineMut) {
+        line.cross_range(self.start..self.end);
+    }
+}
+
+/// A filled cell within reach of the border, which the nearest number must
+/// cover. The block is glued to the cell and extends from it as far from the
+/// border as the number reaches. If the cell is the first one the number can
+/// start at, the block is fixed and capped with a cross.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Glue {
+    start: usize,
+    end: usize,
+    cap: Option<usize>,
+}
+
+impl LineHint for Glue {
+    fn check(&self, line: &dyn Line) -> bool {
+        line.range_contains_unfilled(self.start..self.end)
+            || self.cap.is_some_and(|cap| !line.is_crossed(cap))
+    }
+    fn apply(&self, line: &mut dyn LineMut) {
+        line.fill_range(self.start..self.end);
+        if let Some(cap) = self.cap {
+            line.cross(cap);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EdgeHint {
+    Mercury(Mercury),
+    Glue(Glue),
+}
+
+impl LineHint for EdgeHint {
+    fn check(&self, line: &dyn Line) -> bool {
+        match self {
+            EdgeHint::Mercury(inner) => inner.check(line),
+            EdgeHint::Glue(inner) => inner.check(line),
+        }
+    }
+    fn apply(&self, line: &mut dyn LineMut) {
+        match self {
+            EdgeHint::Mercury(inner) => inner.apply(line),
+            EdgeHint::Glue(inner) => inner.apply(line),
+        }
+    }
+}
+
+/// A line read from its far end.
+struct Reversed<'a>(&'a dyn Line);
+
+impl<'a> Line for Reversed<'a> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+    fn get(&self, i: usize) -> Cell {
+        self.0.get(self.0.len() - 1 - i)
+    }
+    fn is_crossed(&self, i: usize) -> bool {
+        self.0.is_crossed(self.0.len() - 1 - i)
+    }
+    fn is_filled(&self, i: usize) -> bool {
+        self.0.is_filled(self.0.len() - 1 - i)
+    }
+}
+
+/// Mercury and glue for the number nearest the start of the line.
+///
+/// Positions are mapped through `at` before they end up in the hints.
+fn edge_hints<F>(number: usize, line: &dyn Line, at: F) -> Vec<EdgeHint>
+where
+    F: Fn(Range<usize>) -> Range<usize>,
+{
+    let mut hints = vec![];
+    let len = line.len();
+
+    // the first start where the number fits between crosses
+    let mut start = 0;
+    let mut i = 0;
+    while i < start + number {
+        if i >= len {
+            return hints;
+        }
+        if line.is_crossed(i) {
+            start = i + 1;
+        }
+        i += 1;
+    }
+    if line.range_contains_filled(0..start) {
+        return hints;
+    }
+
+    if start > 0 {
+        let range = at(0..start);
+        let mercury = Mercury {
+            start: range.start,
+            end: range.end,
+        };
+        hints.push(EdgeHint::Mercury(mercury));
+    }
+
+    let end = start + number;
+    if let Some(found) = (start..end).find(|i| line.is_filled(*i)) {
+        let range = at(found..end);
+        let cap = if found == start && end < len {
+            Some(at(end..end + 1).start)
+        } else {
+            None
+        };
+        let glue = Glue {
+            start: range.start,
+            end: range.end,
+            cap,
+        };
+        hints.push(EdgeHint::Glue(glue));
+    }
+    hints
+}
+
+#[derive(Debug)]
+pub struct EdgePass;
+
+impl LinePass for EdgePass {
+    type Hint = EdgeHint;
+    fn run(&self, clue: &[usize], line: &dyn Line) -> Vec<Box<Self::Hint>> {
+        let (first, last) = match (clue.first(), clue.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return vec![],
+        };
+        let len = line.len();
+        let mut hints = edge_hints(first, line, |range| range);
+        hints.extend(edge_hints(last, &Reversed(line), |range| {
+            len - range.end..len - range.start
+        }));
+        hints
+            .into_iter()
+            .filter(|hint| hint.check(line))
+            .map(Box::new)
+            .collect()
+    }
+    /// Monochrome only: glue and mercury assume a block ends at a crossed cell
+    /// or the edge, while blocks of different colors may touch.
+    fn run_colored(
+        &self,
+        _clue: &[usize],
+        _colors: &[usize],
+        _line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>> {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use oracle::hints;
+
+    #[test]
+    fn glue() {
+        assert_eq!(
+            hints(&EdgePass, &[3, 1], "#........"),
+            vec![EdgeHint::Glue(Glue {
+                start: 0,
+                end: 3,
+                cap: Some(3),
+            })]
+        );
+        assert_eq!(
+            hints(&EdgePass, &[1, 3], "........#"),
+            vec![EdgeHint::Glue(Glue {
+                start: 6,
+                end: 9,
+                cap: Some(5),
+            })]
+        );
+        assert_eq!(
+            hints(&EdgePass, &[4], "x..#....."),
+            vec![EdgeHint::Glue(Glue {
+                start: 3,
+                end: 5,
+                cap: None,
+            })]
+        );
+    }
+
+    #[test]
+    fn mercury() {
+        assert_eq!(
+            hints(&EdgePass, &[3], ".x..x#...."),
+            vec![
+                EdgeHint::Mercury(Mercury { start: 0, end: 5 }),
+                EdgeHint::Glue(Glue {
+                    start: 5,
+                    end: 8,
+                    cap: Some(8),
+                }),
+            ]
+        );
+        assert_eq!(
+            hints(&EdgePass, &[2, 2], "....x."),
+            vec![EdgeHint::Mercury(Mercury { start: 4, end: 6 })]
+        );
+        assert_eq!(hints(&EdgePass, &[2, 2], "#x...."), vec![]);
+    }
+}
@@ -1,7 +1,15 @@
+mod block_ownership;
+mod complete_line;
 mod continuous_range;
 mod crowded_clue;
 mod discrete_range;
+mod edge;
+mod small_gap;
 
+pub use pass::block_ownership::*;
+pub use pass::complete_line::*;
 pub use pass::continuous_range::*;
 pub use pass::crowded_clue::*;
 pub use pass::discrete_range::*;
+pub use pass::edge::*;
+pub use pass::small_gap::*;
@@ -0,0 +1,153 @@
+use std::fmt;
+
+use pass::ClueExt;
+use puzzle::Line;
+use puzzle::LineHint;
+use puzzle::LineMut;
+use puzzle::LinePass;
+
+/// A gap between crosses that is too small for any number to fit in.
+///
+/// `shortest` is the smallest of the numbers whose range reaches the gap, or
+/// `None` if no number reaches it at all.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SmallGap {
+    start: usize,
+    end: usize,
+    shortest: Option<usize>,
+}
+
+/// Explains why the gap is crossed, e.g. "gap of 2 cells is shorter than
+/// the smallest number 3 that can reach it".
+impl fmt::Display for SmallGap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let len = self.end - self.start;
+        let cells = if len == 1 { "cell" } else { "cells" };
+        match self.shortest {
+            Some(shortest) => write!(
+                f,
+                "gap of {} {} is shorter than the smallest number {} that can reach it",
+                len, cells, shortest
+            ),
+            None => write!(
+                f,
+                "gap of {} {} is out of reach of every number",
+                len, cells
+            ),
+        }
+    }
+}
+
+impl LineHint for SmallGap {
+    fn check(&self, line: &dyn Line) -> bool {
+        line.range_contains_uncrossed(self.start..self.end)
+    }
+    fn apply(&self, line: &mut dyn LineMut) {
+        line.cross_range(self.start..self.end);
+    }
+}
+
+#[derive(Debug)]
+pub struct SmallGapPass;
+
+impl LinePass for SmallGapPass {
+    type Hint = SmallGap;
+    fn run(&self, clue: &[usize], line: &dyn Line) -> Vec<Box<Self::Hint>> {
+        let mut hints = vec![];
+
+        let len = line.len();
+        let (range_starts, mut range_ends) = match (clue.range_starts(line), clue.range_ends(line))
+        {
+            (Some(range_starts), Some(range_ends)) => (range_starts, range_ends),
+            _ => return hints,
+        };
+        range_ends.reverse();
+
+        let mut i = 0;
+        while i < len {
+            if line.is_crossed(i) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < len && !line.is_crossed(i) {
+                i += 1;
+            }
+            if line.range_contains_filled(start..i) {
+                continue;
+            }
+            let shortest = (0..clue.len())
+                .filter(|j| range_starts[*j] < i && start < range_ends[*j])
+                .map(|j| clue[j])
+                .min();
+            if shortest.is_none_or(|shortest| i - start < shortest) {
+                hints.push(Box::new(SmallGap {
+                    start,
+                    end: i,
+                    shortest,
+                }));
+            }
+        }
+        hints
+    }
+    /// Monochrome only: gaps are measured between crossed cells, while blocks
+    /// of different colors may touch.
+    fn run_colored(
+        &self,
+        _clue: &[usize],
+        _colors: &[usize],
+        _line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>> {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use oracle::hints;
+
+    #[test]
+    fn small_gaps() {
+        assert_eq!(
+            hints(&SmallGapPass, &[3], "...x.x..."),
+            vec![SmallGap {
+                start: 4,
+                end: 5,
+                shortest: Some(3),
+            }]
+        );
+        assert_eq!(
+            hints(&SmallGapPass, &[3, 2], "..x...x.x.."),
+            vec![
+                SmallGap {
+                    start: 0,
+                    end: 2,
+                    shortest: None,
+                },
+                SmallGap {
+                    start: 7,
+                    end: 8,
+                    shortest: None,
+                },
+            ]
+        );
+        assert_eq!(hints(&SmallGapPass, &[1, 3], ".x...x..."), vec![]);
+    }
+
+    #[test]
+    fn reasons() {
+        let reasons: Vec<String> = hints(&SmallGapPass, &[3, 2], "x.x...x..x.x..")
+            .iter()
+            .map(|hint| hint.to_string())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "gap of 1 cell is out of reach of every number",
+                "gap of 1 cell is shorter than the smallest number 2 that can reach it",
+            ]
+        );
+    }
+}
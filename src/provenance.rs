@@ -0,0 +1,452 @@
+//! Records which hint decided each cell, to explain how a cell got its value.
+//!
+//! A line pass only looks at one line, so a hint is taken to depend on the
+//! cells of its line that were decided when the pass was run. A lookahead
+//! hint also depends on the decided cells of the lines its probe went
+//! through. Following the hints that decided those cells in turn gives the
+//! chain of hints leading to a cell.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use cache::LineCache;
+use parser::Cell;
+use puzzle;
+use puzzle::Axis;
+use puzzle::Puzzle;
+use solver::Hint;
+use solver::Pass;
+use solver::Schedule;
+use solver::Steps;
+
+/// A hint that decided some cells.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// The number of the task that found the hint, counting from 1.
+    pub round: usize,
+    pub pass: &'static str,
+    pub axis: Axis,
+    pub line: usize,
+    pub hint: String,
+    /// Why the hint holds, if its pass can tell.
+    pub reason: Option<String>,
+    /// The cells the hint changed, as `(x, y)`.
+    pub decided: Vec<(usize, usize)>,
+    /// The cells the hint was found from that were decided before it, as
+    /// `(x, y)`.
+    pub depends: Vec<(usize, usize)>,
+    /// The earlier steps that decided the cells in `depends`.
+    pub requires: Vec<usize>,
+}
+
+fn write_cells(f: &mut fmt::Formatter, cells: &[(usize, usize)]) -> fmt::Result {
+    if cells.is_empty() {
+        return write!(f, " nothing");
+    }
+    for (x, y) in cells {
+        write!(f, " {}", Position { x: *x, y: *y })?;
+    }
+    Ok(())
+}
+
+fn line_name(axis: Axis) -> &'static str {
+    match axis {
+        Axis::Horz => "row",
+        _ => "column",
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "round {}: {} on {} {} decides",
+            self.round,
+            self.pass,
+            line_name(self.axis),
+            self.line + 1
+        )?;
+        write_cells(f, &self.decided)?;
+        write!(f, " from")?;
+        write_cells(f, &self.depends)?;
+        write!(f, "\n  {}", self.hint)?;
+        if let Some(reason) = &self.reason {
+            write!(f, "\n  {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// A cell as given on the command line: column and row, counting from 1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Position {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl FromStr for Position {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let coordinate = |s: Option<&str>| -> Result<usize, String> {
+            s.and_then(|s| s.trim().parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .map(|n| n - 1)
+                .ok_or_else(|| {
+                    format!(
+                        "expected a cell as X,Y counting from 1, got {}",
+                        s.unwrap_or("")
+                    )
+                })
+        };
+        let mut parts = s.splitn(2, ',');
+        let x = coordinate(parts.next())?;
+        let y = coordinate(parts.next())?;
+        Ok(Position { x, y })
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.x + 1, self.y + 1)
+    }
+}
+
+/// The step that last changed each cell of a puzzle.
+#[derive(Clone, Debug)]
+pub struct Provenance {
+    width: usize,
+    steps: Vec<Step>,
+    cells: Vec<Option<usize>>,
+}
+
+fn line_cells(puzzle: &Puzzle, axis: Axis, line: usize) -> Vec<(usize, usize)> {
+    match axis {
+        Axis::Horz => (0..puzzle.width()).map(|x| (x, line)).collect(),
+        _ => (0..puzzle.height()).map(|y| (line, y)).collect(),
+    }
+}
+
+impl Provenance {
+    pub fn new(width: usize, height: usize) -> Self {
+        Provenance {
+            width,
+            steps: vec![],
+            cells: vec![None; width * height],
+        }
+    }
+
+    /// Records what the hints of a task changed, applying them to the puzzle
+    /// as it was before the task.
+    pub fn record(
+        &mut self,
+        puzzle: &mut Puzzle,
+        hints: &[puzzle::Hint<Hint>],
+        pass: &'static str,
+        round: usize,
+    ) {
+        // the cells changed by earlier hints of the task, as they were before
+        let mut before: HashMap<(usize, usize), Cell> = HashMap::new();
+        for hint in hints {
+            let cells = line_cells(puzzle, hint.axis(), hint.line());
+            let old: Vec<Cell> = cells.iter().map(|(x, y)| puzzle.cell(*x, *y)).collect();
+            hint.apply(puzzle);
+            let decided: Vec<(usize, usize)> = cells
+                .iter()
+                .zip(&old)
+                .filter(|((x, y), old)| puzzle.cell(*x, *y) != **old)
+                .map(|(cell, _)| *cell)
+                .collect();
+            if decided.is_empty() {
+                continue;
+            }
+            for (cell, old) in cells.iter().zip(&old) {
+                if decided.contains(cell) {
+                    before.entry(*cell).or_insert(*old);
+                }
+            }
+            let mut read = cells;
+            if let Hint::Lookahead(lookahead) = hint.line_hint() {
+                for (axis, line) in lookahead.read() {
+                    for cell in line_cells(puzzle, *axis, *line) {
+                        if !read.contains(&cell) {
+                            read.push(cell);
+                        }
+                    }
+                }
+            }
+            let depends: Vec<(usize, usize)> = read
+                .into_iter()
+                .filter(|(x, y)| {
+                    let cell = before
+                        .get(&(*x, *y))
+                        .map_or_else(|| puzzle.cell(*x, *y), |cell| *cell);
+                    !matches!(cell, Cell::Undecided | Cell::Masked)
+                })
+                .collect();
+            let mut requires: Vec<usize> = depends
+                .iter()
+                .filter_map(|(x, y)| self.cells[y * self.width + x])
+                .collect();
+            requires.sort_unstable();
+            requires.dedup();
+            for (x, y) in &decided {
+                self.cells[y * self.width + x] = Some(self.steps.len());
+            }
+            self.steps.push(Step {
+                round,
+                pass,
+                axis: hint.axis(),
+                line: hint.line(),
+                hint: format!("{:?}", hint),
+                reason: hint.line_hint().reason(),
+                decided,
+                depends,
+                requires,
+            });
+        }
+    }
+
+    /// Every step that decided some cells, in the order they were taken.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// The step that decided a cell, if any.
+    pub fn step(&self, x: usize, y: usize) -> Option<&Step> {
+        self.cells[y * self.width + x].map(|step| &self.steps[step])
+    }
+
+    /// The steps leading up to a cell, in the order they were taken, ending
+    /// with the one that decided the cell. Cells that were given in the grid
+    /// have no steps.
+    pub fn why(&self, x: usize, y: usize) -> Vec<&Step> {
+        let mut seen = vec![false; self.steps.len()];
+        let mut pending: Vec<usize> = self.cells[y * self.width + x].into_iter().collect();
+        while let Some(step) = pending.pop() {
+            if !seen[step] {
+                seen[step] = true;
+                pending.extend(&self.steps[step].requires);
+            }
+        }
+        self.steps
+            .iter()
+            .zip(seen)
+            .filter(|(_, seen)| *seen)
+            .map(|(step, _)| step)
+            .collect()
+    }
+
+    pub fn dot(&self) -> Dot<'_> {
+        Dot { provenance: self }
+    }
+
+    /// The longest chain of steps that each require the one before, oldest
+    /// first, as indices into `steps`.
+    pub fn critical_path(&self) -> Vec<usize> {
+        let mut depths: Vec<usize> = vec![];
+        for step in &self.steps {
+            let depth = step.requires.iter().map(|r| depths[*r]).max().unwrap_or(0);
+            depths.push(depth + 1);
+        }
+        let mut path = vec![];
+        let mut current = (0..depths.len()).max_by_key(|i| depths[*i]);
+        while let Some(i) = current {
+            path.push(i);
+            current = self.steps[i]
+                .requires
+                .iter()
+                .cloned()
+                .max_by_key(|r| depths[*r]);
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// The steps of a solve as a Graphviz DOT graph.
+///
+/// Each step is a node, with edges to the earlier steps it requires. The
+/// critical path is drawn in red.
+pub struct Dot<'a> {
+    provenance: &'a Provenance,
+}
+
+impl<'a> fmt::Display for Dot<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let steps = &self.provenance.steps;
+        let mut critical = vec![false; steps.len()];
+        // the step before each step of the critical path
+        let mut previous = vec![None; steps.len()];
+        let path = self.provenance.critical_path();
+        for (k, i) in path.iter().enumerate() {
+            critical[*i] = true;
+            if k > 0 {
+                previous[*i] = Some(path[k - 1]);
+            }
+        }
+        writeln!(f, "digraph solve {{")?;
+        writeln!(f, "  rankdir=BT;")?;
+        writeln!(f, "  node [shape=box];")?;
+        for (i, step) in steps.iter().enumerate() {
+            write!(
+                f,
+                "  h{} [label=\"round {}\\n{} on {} {}\\n{} decided\", tooltip=\"{}\"",
+                i,
+                step.round,
+                step.pass,
+                line_name(step.axis),
+                step.line + 1,
+                step.decided.len(),
+                step.hint.replace('\\', "\\\\").replace('"', "\\\"")
+            )?;
+            if critical[i] {
+                write!(f, ", color=red")?;
+            }
+            writeln!(f, "];")?;
+        }
+        for (i, step) in steps.iter().enumerate() {
+            for r in &step.requires {
+                write!(f, "  h{} -> h{}", i, r)?;
+                if previous[i] == Some(*r) {
+                    write!(f, " [color=red]")?;
+                }
+                writeln!(f, ";")?;
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Like `solver::run`, recording the provenance of every cell.
+pub fn run<'a>(
+    puzzle: &mut Puzzle,
+    passes: &'a [Pass],
+    schedule: Box<dyn Schedule<'a> + 'a>,
+    cache: Option<&'a LineCache>,
+    jobs: usize,
+) -> Provenance {
+    let mut provenance = Provenance::new(puzzle.width(), puzzle.height());
+    // follows the puzzle one step behind, without keeping a history
+    let mut replay = puzzle.without_history();
+    for step in Steps::new(puzzle, passes, schedule, cache, jobs) {
+        provenance.record(&mut replay, &step.hints, step.pass.name(), step.round);
+    }
+    provenance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use parser;
+    use solver::Strategy;
+
+    #[test]
+    fn position() {
+        assert_eq!("3,7".parse(), Ok(Position { x: 2, y: 6 }));
+        assert!("0,1".parse::<Position>().is_err());
+        assert!("3".parse::<Position>().is_err());
+        assert_eq!(format!("{}", Position { x: 2, y: 6 }), "3,7");
+    }
+
+    #[test]
+    fn chain() {
+        let ast: parser::Puzzle = "[2;1|1;2|#.;..]".parse().unwrap();
+        let mut puzzle = Puzzle::try_from_ast(ast).unwrap();
+        let passes = Pass::all();
+        let schedule = Strategy::Restart.schedule(&passes, &puzzle);
+        let provenance = run(&mut puzzle, &passes, schedule, None, 1);
+        assert!(puzzle.is_solved());
+        assert!(provenance.step(0, 0).is_none());
+        let last = provenance.step(1, 1).unwrap();
+        let why = provenance.why(1, 1);
+        assert_eq!(why.last(), Some(&last));
+        for (i, step) in why.iter().enumerate() {
+            for (x, y) in &step.depends {
+                if let Some(earlier) = provenance.step(*x, *y) {
+                    assert!(why[..i].contains(&earlier), "{}", step);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lookahead_depends_on_probed_lines() {
+        let ast: parser::Puzzle = "[1;3;4;1,1;1,2,1;3,1;3|1,2;2,2;1,2;1,3;1,1;1,1;3]"
+            .parse()
+            .unwrap();
+        let mut puzzle = Puzzle::try_from_ast(ast).unwrap();
+        let mut passes = Pass::all();
+        passes.push("lookahead".parse().unwrap());
+        let schedule = Strategy::Restart.schedule(&passes, &puzzle);
+        let provenance = run(&mut puzzle, &passes, schedule, None, 1);
+        let lookaheads: Vec<&Step> = provenance
+            .steps()
+            .iter()
+            .filter(|step| step.pass == "lookahead")
+            .collect();
+        assert!(!lookaheads.is_empty());
+        for step in lookaheads {
+            // the probe reads more than the line of the hint
+            assert!(
+                step.depends
+                    .iter()
+                    .any(|cell| !line_cells(&puzzle, step.axis, step.line).contains(cell)),
+                "{}",
+                step
+            );
+            for (x, y) in &step.depends {
+                let earlier = provenance.cells[y * provenance.width + x];
+                assert!(earlier.is_none_or(|earlier| step.requires.contains(&earlier)));
+            }
+        }
+    }
+
+    #[test]
+    fn reasons() {
+        let ast: parser::Puzzle = "[1;5;2,1;2,3;2,1,1;5;1|1;3;2,2;2,2;5;1,1,1;1,3]"
+            .parse()
+            .unwrap();
+        let mut puzzle = Puzzle::try_from_ast(ast).unwrap();
+        let passes = Pass::all();
+        let schedule = Strategy::Restart.schedule(&passes, &puzzle);
+        let provenance = run(&mut puzzle, &passes, schedule, None, 1);
+        let step = provenance
+            .steps()
+            .iter()
+            .find(|step| step.pass == "ownership")
+            .unwrap();
+        let reason = step.reason.as_ref().unwrap();
+        assert!(reason.starts_with("this block is the "), "{}", reason);
+        assert!(step.to_string().ends_with(&format!("\n  {}", reason)));
+        assert!(provenance
+            .steps()
+            .iter()
+            .filter(|step| step.pass == "crowded")
+            .all(|step| step.reason.is_none()));
+    }
+
+    #[test]
+    fn dot() {
+        let ast: parser::Puzzle = "[1,1,2;1,2;6;1|3;2;1,1;2;3;1,1]".parse().unwrap();
+        let mut puzzle = Puzzle::try_from_ast(ast).unwrap();
+        let passes = Pass::all();
+        let schedule = Strategy::Restart.schedule(&passes, &puzzle);
+        let provenance = run(&mut puzzle, &passes, schedule, None, 1);
+        let path = provenance.critical_path();
+        assert!(path.len() > 1);
+        for pair in path.windows(2) {
+            assert!(provenance.steps[pair[1]].requires.contains(&pair[0]));
+        }
+        let dot = format!("{}", provenance.dot());
+        assert!(dot.starts_with("digraph solve {"));
+        let edges: usize = provenance
+            .steps
+            .iter()
+            .map(|step| step.requires.len())
+            .sum();
+        assert_eq!(dot.matches(" -> ").count(), edges);
+        assert_eq!(dot.matches("[color=red]").count(), path.len() - 1);
+    }
+}
@@ -2,20 +2,29 @@ use fixedbitset::FixedBitSet;
 use std::borrow::Borrow;
 use std::borrow::Cow;
 use std::fmt;
+use std::mem;
 use std::ops::Range;
 use std::str::FromStr;
+use std::thread;
 
+use accounting::ClueAccounting;
+use lookahead::Lookahead;
+use lookahead::LookaheadPass;
 use parser;
 use parser::Cell;
+use parser::Clue;
 use parser::ClueList;
 use parser::GridLine;
+use parser::DEFAULT_COLOR;
+use solver::Pass;
 
 pub trait LineHint: fmt::Debug {
-    fn check(&self, line: &Line) -> bool;
-    fn apply(&self, line: &mut LineMut);
+    fn check(&self, line: &dyn Line) -> bool;
+    fn apply(&self, line: &mut dyn LineMut);
 }
 
-#[derive(Clone, Copy, Debug)]
+/// A direction of lines through a rectangular grid.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Axis {
     Horz,
     Vert,
@@ -29,102 +38,225 @@ impl Axis {
     }
 }
 
+/// The directions the lines of a kind of puzzle run in.
+pub trait Axes: Copy + fmt::Debug + Eq + Send + Sync + 'static {
+    /// Every axis, in the order passes take turns on them.
+    fn all() -> &'static [Self];
+    fn name(self) -> &'static str;
+    /// The position of the axis in `all`.
+    fn index(self) -> usize {
+        Self::all().iter().position(|axis| *axis == self).unwrap()
+    }
+}
+
+impl Axes for Axis {
+    fn all() -> &'static [Axis] {
+        &ORIENTATIONS
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Axis::Horz => "Horz",
+            Axis::Vert => "Vert",
+        }
+    }
+}
+
+/// A puzzle made of lines with clues, along the axes of its kind.
+///
+/// Line passes and the solver work on any board, so rectangular puzzles and
+/// triddlers share them.
+pub trait Board: Sync {
+    type Axis: Axes;
+    /// Number of lines along an axis.
+    fn lines(&self, axis: Self::Axis) -> usize;
+    /// The numbers of the clue of a line, and the palette index of each in
+    /// colored puzzles.
+    fn clue(&self, axis: Self::Axis, line: usize) -> (&[usize], &[usize]);
+    fn is_colored(&self) -> bool;
+    /// Whether no pass has anything left to find on the line.
+    fn is_finished(&self, _axis: Self::Axis, _line: usize) -> bool {
+        false
+    }
+    /// Calls `f` with a line of the board.
+    fn with_line<R, F: FnOnce(&dyn Line) -> R>(&self, axis: Self::Axis, line: usize, f: F) -> R;
+    /// Applies a line hint to a line.
+    fn apply(&mut self, axis: Self::Axis, line: usize, hint: &dyn LineHint);
+    /// The lines along each axis, in the order of `Axes::all`, with cells
+    /// changed since the last call.
+    fn take_changed_lines(&mut self) -> Vec<FixedBitSet>;
+    fn is_complete(&self) -> bool;
+    /// Number of cells that are filled or crossed.
+    fn decided(&self) -> usize;
+    /// Runs the lookahead pass, which needs the whole board rather than a
+    /// line, following its assumptions with `passes`. Boards that cannot be
+    /// probed find nothing.
+    fn lookahead(
+        &self,
+        _pass: &LookaheadPass,
+        _passes: &[Pass],
+        _axis: Self::Axis,
+        _lines: &FixedBitSet,
+    ) -> Vec<Hint<Lookahead, Self::Axis>> {
+        vec![]
+    }
+}
+
 #[derive(Debug)]
-pub struct Hint<H: LineHint> {
-    axis: Axis,
+pub struct Hint<H: LineHint, A = Axis> {
+    axis: A,
     line: usize,
     line_hint: Box<H>,
 }
 
-impl<H: LineHint> Hint<H> {
-    pub fn apply<'a>(&self, puzzle: &mut Puzzle<'a>) {
-        match self.axis {
-            Axis::Vert => {
-                self.line_hint.apply(&mut VertLineMut {
-                    grid: &mut puzzle.grid,
-                    x: self.line,
-                });
-            }
-            Axis::Horz => {
-                self.line_hint.apply(&mut HorzLineMut {
-                    grid: &mut puzzle.grid,
-                    y: self.line,
-                });
-            }
+impl<H: LineHint, A: Axes> Hint<H, A> {
+    pub fn new(axis: A, line: usize, line_hint: H) -> Self {
+        Hint {
+            axis,
+            line,
+            line_hint: Box::new(line_hint),
+        }
+    }
+    pub fn axis(&self) -> A {
+        self.axis
+    }
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn line_hint(&self) -> &H {
+        &self.line_hint
+    }
+    /// Converts the line hint, e.g. to wrap it in an enum of hints.
+    pub fn map<G: LineHint, F: FnOnce(H) -> G>(self, f: F) -> Hint<G, A> {
+        Hint {
+            axis: self.axis,
+            line: self.line,
+            line_hint: Box::new(f(*self.line_hint)),
         }
     }
+    pub fn apply<B: Board<Axis = A>>(&self, puzzle: &mut B) {
+        puzzle.apply(self.axis, self.line, &*self.line_hint);
+    }
 }
 
 pub trait LinePass: fmt::Debug {
     type Hint: LineHint;
-    fn run(&self, clue: &[usize], line: &Line) -> Vec<Box<Self::Hint>>;
+    fn run(&self, clue: &[usize], line: &dyn Line) -> Vec<Box<Self::Hint>>;
+    /// Like `run`, for lines of colored puzzles.
+    ///
+    /// `colors` holds the palette index of each number in the clue. Passes
+    /// that only know about monochrome lines find nothing here, and say so.
+    fn run_colored(
+        &self,
+        clue: &[usize],
+        colors: &[usize],
+        line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>>;
 }
 
+/// Runs a line pass over the given lines of one axis of a puzzle.
 pub trait LinePassExt<H: LineHint> {
-    fn run_vert(&self, puzzle: &Puzzle) -> Vec<Hint<H>>;
-    fn run_horz(&self, puzzle: &Puzzle) -> Vec<Hint<H>>;
-    fn run_puzzle(&self, axis: &Axis, puzzle: &Puzzle) -> Vec<Hint<H>> {
-        match axis {
-            Axis::Vert => self.run_vert(puzzle),
-            Axis::Horz => self.run_horz(puzzle),
+    fn run_puzzle<B: Board>(
+        &self,
+        axis: &B::Axis,
+        puzzle: &B,
+        lines: &FixedBitSet,
+    ) -> Vec<Hint<H, B::Axis>>;
+    /// Like `run_puzzle`, but splits the lines between `jobs` threads.
+    fn run_puzzle_parallel<B: Board>(
+        &self,
+        axis: &B::Axis,
+        puzzle: &B,
+        lines: &FixedBitSet,
+        jobs: usize,
+    ) -> Vec<Hint<H, B::Axis>>
+    where
+        Self: Sync,
+        H: Send,
+    {
+        let indices: Vec<usize> = lines.ones().collect();
+        if jobs <= 1 || indices.len() <= 1 {
+            return self.run_puzzle(axis, puzzle, lines);
         }
+        let chunk_size = indices.len().div_ceil(jobs);
+        thread::scope(|scope| {
+            let handles: Vec<_> = indices
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let mut chunk_lines = FixedBitSet::with_capacity(lines.len());
+                    chunk_lines.extend(chunk.iter().cloned());
+                    scope.spawn(move || self.run_puzzle(axis, puzzle, &chunk_lines))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
     }
-    fn apply(&self, axis: &Axis, puzzle: &mut Puzzle) -> Vec<Hint<H>> {
-        let hints = self.run_puzzle(axis, puzzle);
+    fn apply<B: Board>(
+        &self,
+        axis: &B::Axis,
+        puzzle: &mut B,
+        lines: &FixedBitSet,
+    ) -> Vec<Hint<H, B::Axis>> {
+        let hints = self.run_puzzle(axis, puzzle, lines);
         for hint in &hints {
             hint.apply(puzzle);
         }
-        // println!( "\nAfter {:?} line:\n{}", axis, Theme::Unicode.view(puzzle));
         hints
     }
 }
 
 impl<H: LineHint, T: LinePass<Hint = H>> LinePassExt<H> for T {
-    fn run_vert(&self, puzzle: &Puzzle) -> Vec<Hint<H>> {
+    fn run_puzzle<B: Board>(
+        &self,
+        axis: &B::Axis,
+        puzzle: &B,
+        lines: &FixedBitSet,
+    ) -> Vec<Hint<H, B::Axis>> {
         let mut hints = vec![];
-        for (x, clue) in puzzle.vert_clues.0.iter().enumerate() {
-            let mut line = VertLine {
-                grid: &puzzle.grid,
-                x,
-            };
-            for line_hint in self.run(clue.0.as_slice(), &line) {
-                let hint = Hint {
-                    axis: Axis::Vert,
-                    line: x,
-                    line_hint,
-                };
-                hints.push(hint);
+        for line in lines.ones() {
+            if puzzle.is_finished(*axis, line) {
+                continue;
             }
-        }
-        hints
-    }
-
-    fn run_horz(&self, puzzle: &Puzzle) -> Vec<Hint<H>> {
-        let mut hints = vec![];
-        for (y, clue) in puzzle.horz_clues.0.iter().enumerate() {
-            let mut line = HorzLine {
-                grid: &puzzle.grid,
-                y,
-            };
-            for line_hint in self.run(clue.0.as_slice(), &line) {
-                let hint = Hint {
-                    axis: Axis::Horz,
-                    line: y,
+            let (clue, colors) = puzzle.clue(*axis, line);
+            let line_hints = puzzle.with_line(*axis, line, |cells| {
+                if puzzle.is_colored() {
+                    self.run_colored(clue, colors, cells)
+                } else {
+                    self.run(clue, cells)
+                }
+            });
+            for line_hint in line_hints {
+                hints.push(Hint {
+                    axis: *axis,
+                    line,
                     line_hint,
-                };
-                hints.push(hint);
+                });
             }
         }
         hints
     }
 }
 
+#[allow(clippy::len_without_is_empty)]
 pub trait Line {
     fn len(&self) -> usize;
     fn get(&self, i: usize) -> Cell;
     fn is_crossed(&self, i: usize) -> bool;
     fn is_filled(&self, i: usize) -> bool;
 
+    /// Number of colors in the palette of a colored puzzle, zero for
+    /// monochrome ones.
+    fn colors(&self) -> usize {
+        0
+    }
+    /// Whether the cell has been ruled out from having the color with the
+    /// given palette index.
+    fn is_excluded(&self, _i: usize, _color: usize) -> bool {
+        false
+    }
+
     fn range_contains_filled(&self, r: Range<usize>) -> bool {
         for i in r {
             if self.is_filled(i) {
@@ -152,6 +284,46 @@ pub trait Line {
         false
     }
 
+    fn filled_runs(&self) -> Vec<usize> {
+        let mut runs = vec![];
+        let mut run = 0;
+        for i in 0..self.len() {
+            if self.is_filled(i) {
+                run += 1;
+            } else if run > 0 {
+                runs.push(run);
+                run = 0;
+            }
+        }
+        if run > 0 {
+            runs.push(run);
+        }
+        runs
+    }
+
+    /// Runs of filled cells along with their colors, for lines where every
+    /// filled cell has a known color.
+    fn colored_runs(&self) -> Vec<(usize, char)> {
+        let mut runs: Vec<(usize, char)> = vec![];
+        let mut prev = None;
+        for i in 0..self.len() {
+            let color = match self.get(i) {
+                Cell::Filled => Some(DEFAULT_COLOR),
+                Cell::Colored(color) => Some(color),
+                _ => None,
+            };
+            match (color, runs.last_mut()) {
+                (Some(color), Some((run, last))) if prev == Some(color) && *last == color => {
+                    *run += 1;
+                }
+                (Some(color), _) => runs.push((1, color)),
+                (None, _) => {}
+            }
+            prev = color;
+        }
+        runs
+    }
+
     fn bump_start(&self, start: usize, number: usize) -> usize {
         //println!("BUMP START {} {}", start, number);
         //if start > 0 { println!("  check filled {}", start - 1); }
@@ -187,7 +359,7 @@ pub trait Line {
         //println!("  ends last {}", last);
         //println!("  ends number {}", number);
         let mut focus: isize = last;
-        while focus >= 0 && focus + number >= last + 1 {
+        while focus >= 0 && focus + number > last {
             if self.is_crossed(focus as usize) {
                 // pushing cross
                 //println!("  ends pushed by cross at {}", focus);
@@ -207,16 +379,16 @@ pub trait Line {
 }
 
 pub trait LineExt: Line {
-    fn view(&self) -> LineView;
+    fn view(&self) -> LineView<'_>;
 }
 
 impl<T: Line> LineExt for T {
-    fn view(&self) -> LineView {
+    fn view(&self) -> LineView<'_> {
         LineView(self)
     }
 }
 
-pub struct LineView<'a>(&'a Line);
+pub struct LineView<'a>(&'a dyn Line);
 
 impl<'a> fmt::Display for LineView<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -228,8 +400,10 @@ impl<'a> fmt::Display for LineView<'a> {
 }
 
 pub trait LineMut: Line {
-    fn cross(&mut self, i: usize);
-    fn fill(&mut self, i: usize);
+    fn cross(&mut self, i: usize) -> bool;
+    fn fill(&mut self, i: usize) -> bool;
+    /// Rules out the color with the given palette index for the cell.
+    fn exclude(&mut self, i: usize, color: usize) -> bool;
 
     fn cross_range(&mut self, r: Range<usize>) {
         for i in r {
@@ -253,6 +427,12 @@ impl<'a> Line for HorzLine<'a> {
     fn get(&self, x: usize) -> Cell {
         self.grid.get(x, self.y)
     }
+    fn colors(&self) -> usize {
+        self.grid.palette.len()
+    }
+    fn is_excluded(&self, x: usize, color: usize) -> bool {
+        self.grid.is_excluded(x, self.y, color)
+    }
     fn is_crossed(&self, x: usize) -> bool {
         self.grid.is_crossed(x, self.y)
     }
@@ -267,12 +447,20 @@ impl<'a> Line for HorzLine<'a> {
 pub struct HorzLineMut<'a> {
     grid: &'a mut Grid,
     y: usize,
+    /// Where to note the bits set through the line, if anywhere.
+    journal: Option<&'a mut Vec<Edit>>,
 }
 
 impl<'a> Line for HorzLineMut<'a> {
     fn get(&self, x: usize) -> Cell {
         self.grid.get(x, self.y)
     }
+    fn colors(&self) -> usize {
+        self.grid.palette.len()
+    }
+    fn is_excluded(&self, x: usize, color: usize) -> bool {
+        self.grid.is_excluded(x, self.y, color)
+    }
     fn is_crossed(&self, x: usize) -> bool {
         self.grid.is_crossed(x, self.y)
     }
@@ -285,11 +473,22 @@ impl<'a> Line for HorzLineMut<'a> {
 }
 
 impl<'a> LineMut for HorzLineMut<'a> {
-    fn cross(&mut self, x: usize) {
-        self.grid.cross(x, self.y);
+    fn cross(&mut self, x: usize) -> bool {
+        let i = self.grid.index(x, self.y);
+        note(
+            &mut self.journal,
+            self.grid.cross(x, self.y),
+            Edit::Cross(i),
+        )
+    }
+    fn exclude(&mut self, x: usize, color: usize) -> bool {
+        let i = self.grid.index(x, self.y);
+        let changed = self.grid.exclude(x, self.y, color);
+        note(&mut self.journal, changed, Edit::Exclude(i, color))
     }
-    fn fill(&mut self, x: usize) {
-        self.grid.fill(x, self.y);
+    fn fill(&mut self, x: usize) -> bool {
+        let i = self.grid.index(x, self.y);
+        note(&mut self.journal, self.grid.fill(x, self.y), Edit::Fill(i))
     }
 }
 
@@ -302,6 +501,12 @@ impl<'a> Line for VertLine<'a> {
     fn get(&self, y: usize) -> Cell {
         self.grid.get(self.x, y)
     }
+    fn colors(&self) -> usize {
+        self.grid.palette.len()
+    }
+    fn is_excluded(&self, y: usize, color: usize) -> bool {
+        self.grid.is_excluded(self.x, y, color)
+    }
     fn is_crossed(&self, y: usize) -> bool {
         self.grid.is_crossed(self.x, y)
     }
@@ -316,12 +521,20 @@ impl<'a> Line for VertLine<'a> {
 pub struct VertLineMut<'a> {
     grid: &'a mut Grid,
     x: usize,
+    /// Where to note the bits set through the line, if anywhere.
+    journal: Option<&'a mut Vec<Edit>>,
 }
 
 impl<'a> Line for VertLineMut<'a> {
     fn get(&self, y: usize) -> Cell {
         self.grid.get(self.x, y)
     }
+    fn colors(&self) -> usize {
+        self.grid.palette.len()
+    }
+    fn is_excluded(&self, y: usize, color: usize) -> bool {
+        self.grid.is_excluded(self.x, y, color)
+    }
     fn is_crossed(&self, y: usize) -> bool {
         self.grid.is_crossed(self.x, y)
     }
@@ -334,89 +547,431 @@ impl<'a> Line for VertLineMut<'a> {
 }
 
 impl<'a> LineMut for VertLineMut<'a> {
-    fn cross(&mut self, y: usize) {
-        self.grid.cross(self.x, y);
+    fn cross(&mut self, y: usize) -> bool {
+        let i = self.grid.index(self.x, y);
+        note(
+            &mut self.journal,
+            self.grid.cross(self.x, y),
+            Edit::Cross(i),
+        )
+    }
+    fn exclude(&mut self, y: usize, color: usize) -> bool {
+        let i = self.grid.index(self.x, y);
+        let changed = self.grid.exclude(self.x, y, color);
+        note(&mut self.journal, changed, Edit::Exclude(i, color))
     }
-    fn fill(&mut self, y: usize) {
-        self.grid.fill(self.x, y);
+    fn fill(&mut self, y: usize) -> bool {
+        let i = self.grid.index(self.x, y);
+        note(&mut self.journal, self.grid.fill(self.x, y), Edit::Fill(i))
     }
 }
 
+/// Notes the edit in the journal if it changed the grid, returning whether it
+/// did.
+fn note(journal: &mut Option<&mut Vec<Edit>>, changed: bool, edit: Edit) -> bool {
+    if let (true, Some(journal)) = (changed, journal) {
+        journal.push(edit);
+    }
+    changed
+}
+
+#[derive(Clone)]
 pub struct Grid {
     width: usize,
     height: usize,
     filled: FixedBitSet,
     crossed: FixedBitSet,
+    /// The colors of a colored puzzle, empty for monochrome ones.
+    palette: Vec<char>,
+    /// The cells ruled out for each color in the palette.
+    excluded: Vec<FixedBitSet>,
+    /// The cells outside the board. These are also crossed.
+    masked: FixedBitSet,
+    changes: Changes,
+}
+
+/// A bit of the grid that was set, so that it can be cleared again.
+#[derive(Clone, Copy, Debug)]
+enum Edit {
+    Fill(usize),
+    Cross(usize),
+    Exclude(usize, usize),
+}
+
+impl Edit {
+    fn index(self) -> usize {
+        match self {
+            Edit::Fill(i) | Edit::Cross(i) | Edit::Exclude(i, _) => i,
+        }
+    }
 }
 
 impl Grid {
     pub fn new(width: usize, height: usize) -> Self {
+        Grid::with_cells(
+            width,
+            height,
+            FixedBitSet::with_capacity(width * height),
+            FixedBitSet::with_capacity(width * height),
+        )
+    }
+    /// An empty grid for a colored puzzle with the given palette.
+    pub fn colored(width: usize, height: usize, palette: Vec<char>) -> Self {
+        let mut grid = Grid::new(width, height);
+        grid.excluded = vec![FixedBitSet::with_capacity(width * height); palette.len()];
+        grid.palette = palette;
+        grid
+    }
+    fn with_cells(width: usize, height: usize, filled: FixedBitSet, crossed: FixedBitSet) -> Self {
         Grid {
             width,
             height,
-            filled: FixedBitSet::with_capacity(width * height),
-            crossed: FixedBitSet::with_capacity(width * height),
+            filled,
+            crossed,
+            palette: vec![],
+            excluded: vec![],
+            masked: FixedBitSet::with_capacity(width * height),
+            changes: Changes::new(width, height),
         }
     }
-    pub fn horz_mut(&mut self, y: usize) -> HorzLineMut {
-        HorzLineMut { grid: self, y }
+    pub fn horz_mut(&mut self, y: usize) -> HorzLineMut<'_> {
+        HorzLineMut {
+            grid: self,
+            y,
+            journal: None,
+        }
     }
     fn index(&self, x: usize, y: usize) -> usize {
         assert!(x < self.width);
         assert!(y < self.height);
         y * self.width + x
     }
-    fn get(&self, x: usize, y: usize) -> Cell {
+    pub fn get(&self, x: usize, y: usize) -> Cell {
         let i = self.index(x, y);
         match (self.filled.contains(i), self.crossed.contains(i)) {
+            (false, true) if self.masked.contains(i) => Cell::Masked,
             (false, false) => Cell::Undecided,
             (false, true) => Cell::Crossed,
-            (true, false) => Cell::Filled,
+            (true, false) if self.palette.is_empty() => Cell::Filled,
+            (true, false) => {
+                let mut colors = self
+                    .palette
+                    .iter()
+                    .zip(&self.excluded)
+                    .filter(|(_, excluded)| !excluded.contains(i));
+                match (colors.next(), colors.next()) {
+                    (None, _) => Cell::Impossible,
+                    (Some((&DEFAULT_COLOR, _)), None) => Cell::Filled,
+                    (Some((color, _)), None) => Cell::Colored(*color),
+                    (Some(_), Some(_)) => Cell::Unresolved,
+                }
+            }
             (true, true) => Cell::Impossible,
         }
     }
-    fn fill(&mut self, x: usize, y: usize) -> bool {
+    pub fn fill(&mut self, x: usize, y: usize) -> bool {
         let i = self.index(x, y);
-        let old_value = self.filled.contains(i);
-        self.filled.put(i);
+        let old_value = self.filled.put(i);
+        if !old_value {
+            self.changes.touch(x, y);
+        }
         !old_value
     }
-    fn cross(&mut self, x: usize, y: usize) -> bool {
+    pub fn cross(&mut self, x: usize, y: usize) -> bool {
         let i = self.index(x, y);
-        let old_value = self.crossed.contains(i);
-        self.crossed.put(i);
+        let old_value = self.crossed.put(i);
+        if !old_value {
+            self.changes.touch(x, y);
+        }
         !old_value
     }
-    fn is_crossed(&self, x: usize, y: usize) -> bool {
+    /// Takes the cell out of the board, crossing it for good.
+    pub fn mask(&mut self, x: usize, y: usize) {
+        let i = self.index(x, y);
+        self.masked.put(i);
+        self.cross(x, y);
+    }
+    pub fn exclude(&mut self, x: usize, y: usize, color: usize) -> bool {
+        let i = self.index(x, y);
+        let old_value = self.excluded[color].put(i);
+        if !old_value {
+            self.changes.touch(x, y);
+        }
+        !old_value
+    }
+    pub fn is_crossed(&self, x: usize, y: usize) -> bool {
         let i = self.index(x, y);
         self.crossed.contains(i)
     }
-    fn is_filled(&self, x: usize, y: usize) -> bool {
+    pub fn is_excluded(&self, x: usize, y: usize, color: usize) -> bool {
+        let i = self.index(x, y);
+        self.excluded[color].contains(i)
+    }
+    pub fn is_filled(&self, x: usize, y: usize) -> bool {
         let i = self.index(x, y);
         self.filled.contains(i)
     }
+    /// Returns the rows and columns touched since the last call.
+    pub fn take_changes(&mut self) -> Changes {
+        let changes = Changes::new(self.width, self.height);
+        mem::replace(&mut self.changes, changes)
+    }
+    /// Clears the bits set by the edits, newest first.
+    fn revert(&mut self, edits: &[Edit]) {
+        for edit in edits.iter().rev() {
+            match *edit {
+                Edit::Fill(i) => self.filled.set(i, false),
+                Edit::Cross(i) => self.crossed.set(i, false),
+                Edit::Exclude(i, color) => self.excluded[color].set(i, false),
+            }
+            let i = edit.index();
+            self.changes.touch(i % self.width, i / self.width);
+        }
+    }
+    /// Sets the bits of the edits again, in order, and returns the ones that
+    /// were not set already.
+    fn replay(&mut self, edits: &[Edit]) -> Vec<Edit> {
+        let mut replayed = vec![];
+        for edit in edits {
+            let i = edit.index();
+            let (x, y) = (i % self.width, i / self.width);
+            let changed = match *edit {
+                Edit::Fill(_) => self.fill(x, y),
+                Edit::Cross(_) => self.cross(x, y),
+                Edit::Exclude(_, color) => self.exclude(x, y, color),
+            };
+            if changed {
+                replayed.push(*edit);
+            }
+        }
+        replayed
+    }
+}
+
+/// Rows and columns with cells that have been filled, crossed or had a color
+/// ruled out.
+#[derive(Clone, Debug)]
+pub struct Changes {
+    pub horz: FixedBitSet,
+    pub vert: FixedBitSet,
 }
 
+impl Changes {
+    pub fn new(width: usize, height: usize) -> Self {
+        Changes {
+            horz: FixedBitSet::with_capacity(height),
+            vert: FixedBitSet::with_capacity(width),
+        }
+    }
+    fn touch(&mut self, x: usize, y: usize) {
+        self.horz.put(y);
+        self.vert.put(x);
+    }
+}
+
+#[derive(Clone)]
 pub struct Puzzle<'a> {
     vert_clues: Cow<'a, ClueList>,
     horz_clues: Cow<'a, ClueList>,
+    /// Palette index of each number in the vertical clues, empty for
+    /// monochrome puzzles.
+    vert_colors: Vec<Vec<usize>>,
+    /// Palette index of each number in the horizontal clues, empty for
+    /// monochrome puzzles.
+    horz_colors: Vec<Vec<usize>>,
     grid: Grid,
+    /// The placed numbers of each line, as of the last `take_changes`.
+    accounting: ClueAccounting,
+    /// Every bit set by the applied hints, oldest first.
+    journal: Vec<Edit>,
+    /// Where the edits of each applied hint start in the journal.
+    history: Vec<usize>,
+    /// The edits of undone hints, most recently undone last.
+    undone: Vec<Vec<Edit>>,
 }
 
+/// A point in the history of a puzzle, to go back to with `Puzzle::restore`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Snapshot(usize);
+
 impl<'a> Puzzle<'a> {
+    pub fn width(&self) -> usize {
+        self.grid.width
+    }
+    pub fn height(&self) -> usize {
+        self.grid.height
+    }
+    /// Returns the rows and columns touched since the last call, and recounts
+    /// their placed numbers.
+    pub fn take_changes(&mut self) -> Changes {
+        let changes = self.grid.take_changes();
+        self.account(changes.horz.ones(), changes.vert.ones());
+        changes
+    }
+    fn account<H, V>(&mut self, horz: H, vert: V)
+    where
+        H: Iterator<Item = usize>,
+        V: Iterator<Item = usize>,
+    {
+        for y in horz {
+            let line = HorzLine {
+                grid: &self.grid,
+                y,
+            };
+            let colors = self.horz_colors.get(y).map_or(&[][..], |colors| colors);
+            self.accounting
+                .update(Axis::Horz, y, &self.horz_clues.0[y].numbers, colors, &line);
+        }
+        for x in vert {
+            let line = VertLine {
+                grid: &self.grid,
+                x,
+            };
+            let colors = self.vert_colors.get(x).map_or(&[][..], |colors| colors);
+            self.accounting
+                .update(Axis::Vert, x, &self.vert_clues.0[x].numbers, colors, &line);
+        }
+    }
+    /// Recounts the placed numbers of the lines with cells in the edits.
+    fn account_edits(&mut self, edits: &[Edit]) {
+        let mut lines = Changes::new(self.width(), self.height());
+        for edit in edits {
+            let i = edit.index();
+            lines.touch(i % self.width(), i / self.width());
+        }
+        self.account(lines.horz.ones(), lines.vert.ones());
+    }
+    /// Takes back the cells changed by the last applied hint. Returns false
+    /// if there is nothing to undo.
+    ///
+    /// The rows and columns of the cells count as changed.
+    pub fn undo(&mut self) -> bool {
+        let start = match self.history.pop() {
+            Some(start) => start,
+            None => return false,
+        };
+        let edits = self.journal.split_off(start);
+        self.grid.revert(&edits);
+        self.account_edits(&edits);
+        self.undone.push(edits);
+        true
+    }
+    /// Applies the last undone hint again. Returns false if there is nothing
+    /// to redo, which is also the case once another hint has been applied.
+    pub fn redo(&mut self) -> bool {
+        let edits = match self.undone.pop() {
+            Some(edits) => edits,
+            None => return false,
+        };
+        self.history.push(self.journal.len());
+        let edits = self.grid.replay(&edits);
+        self.account_edits(&edits);
+        self.journal.extend(edits);
+        true
+    }
+    /// The current point in the history of the puzzle.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(self.journal.len())
+    }
+    /// A copy of the puzzle as it is now, with none of its history.
+    pub fn without_history(&self) -> Puzzle<'a> {
+        Puzzle {
+            vert_clues: self.vert_clues.clone(),
+            horz_clues: self.horz_clues.clone(),
+            vert_colors: self.vert_colors.clone(),
+            horz_colors: self.horz_colors.clone(),
+            grid: self.grid.clone(),
+            accounting: self.accounting.clone(),
+            journal: vec![],
+            history: vec![],
+            undone: vec![],
+        }
+    }
+    /// Takes back every cell changed since the snapshot was taken, dropping
+    /// the hints applied since from the history and forgetting what can be
+    /// redone.
+    ///
+    /// The snapshot must not have been undone past in the meantime. The rows
+    /// and columns of the cells count as changed.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        assert!(
+            snapshot.0 <= self.journal.len(),
+            "snapshot is no longer in the history"
+        );
+        let edits = self.journal.split_off(snapshot.0);
+        self.grid.revert(&edits);
+        self.account_edits(&edits);
+        self.history.retain(|start| *start < snapshot.0);
+        self.undone.clear();
+    }
+    /// The numbers of the clue of a line that are known to be placed.
+    pub fn satisfied(&self, axis: Axis, line: usize) -> &FixedBitSet {
+        self.accounting.satisfied(axis, line)
+    }
+    /// Whether the line is fully decided with every number placed.
+    pub fn is_finished(&self, axis: Axis, line: usize) -> bool {
+        self.accounting.is_finished(axis, line)
+    }
+    pub fn cell(&self, x: usize, y: usize) -> Cell {
+        self.grid.get(x, y)
+    }
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+    pub fn is_colored(&self) -> bool {
+        !self.grid.palette.is_empty()
+    }
     pub fn is_complete(&self) -> bool {
         for i in 0..self.grid.filled.len() {
             if !self.grid.filled.contains(i) && !self.grid.crossed.contains(i) {
                 return false;
             }
         }
-        true
+        !self.is_colored() || !self.cells().any(|cell| cell == Cell::Unresolved)
+    }
+    fn cells<'b>(&'b self) -> impl Iterator<Item = Cell> + 'b {
+        let w = self.width();
+        (0..w * self.height()).map(move |i| self.grid.get(i % w, i / w))
+    }
+    /// Number of cells that are filled or crossed.
+    pub fn decided(&self) -> usize {
+        self.grid.filled.union(&self.grid.crossed).count()
+    }
+    pub fn is_contradictory(&self) -> bool {
+        if self.is_colored() {
+            return self.cells().any(|cell| cell == Cell::Impossible);
+        }
+        self.grid
+            .filled
+            .intersection(&self.grid.crossed)
+            .next()
+            .is_some()
+    }
+    pub fn is_solved(&self) -> bool {
+        if !self.is_complete() || self.is_contradictory() {
+            return false;
+        }
+        let horz_ok = self.horz_clues.0.iter().enumerate().all(|(y, clue)| {
+            let line = HorzLine {
+                grid: &self.grid,
+                y,
+            };
+            line_is_solved(&line, clue)
+        });
+        let vert_ok = self.vert_clues.0.iter().enumerate().all(|(x, clue)| {
+            let line = VertLine {
+                grid: &self.grid,
+                x,
+            };
+            line_is_solved(&line, clue)
+        });
+        horz_ok && vert_ok
     }
     fn max_horz_clue_len(&self) -> usize {
         self.horz_clues
             .0
             .iter()
-            .map(|clue| clue.0.len())
+            .map(|clue| clue.numbers.len())
             .max()
             .unwrap()
     }
@@ -424,13 +979,76 @@ impl<'a> Puzzle<'a> {
         self.vert_clues
             .0
             .iter()
-            .map(|clue| clue.0.len())
+            .map(|clue| clue.numbers.len())
             .max()
             .unwrap()
     }
     pub fn try_from_ast(ast: parser::Puzzle<'a>) -> Result<Puzzle<'a>, String> {
         let w = ast.vert_clues.0.len();
         let h = ast.horz_clues.0.len();
+        for (name, clues, len) in &[
+            ("vertical", &ast.vert_clues, h),
+            ("horizontal", &ast.horz_clues, w),
+        ] {
+            for (i, clue) in clues.0.iter().enumerate() {
+                if clue.has_zero() {
+                    return Err(format!("{} clue {} ({}) has a zero", name, i + 1, clue));
+                }
+                // only neighbouring numbers of the same color need a gap
+                let gaps = clue
+                    .colors
+                    .windows(2)
+                    .filter(|pair| pair[0] == pair[1])
+                    .count();
+                let needed = clue
+                    .numbers
+                    .iter()
+                    .try_fold(gaps, |sum, n| sum.checked_add(*n));
+                if needed.is_none_or(|needed| needed > *len) {
+                    return Err(format!(
+                        "{} clue {} ({}) does not fit in {} cells",
+                        name,
+                        i + 1,
+                        clue,
+                        len
+                    ));
+                }
+            }
+        }
+        let mut palette: Vec<char> = vec![];
+        let is_colored = ast
+            .vert_clues
+            .0
+            .iter()
+            .chain(&ast.horz_clues.0)
+            .any(Clue::is_colored);
+        if is_colored {
+            for clue in ast.vert_clues.0.iter().chain(&ast.horz_clues.0) {
+                for color in &clue.colors {
+                    if !palette.contains(color) {
+                        palette.push(*color);
+                    }
+                }
+            }
+        }
+        let colors = |clues: &ClueList| -> Vec<Vec<usize>> {
+            if !is_colored {
+                return vec![];
+            }
+            clues
+                .0
+                .iter()
+                .map(|clue| {
+                    clue.colors
+                        .iter()
+                        .map(|color| palette.iter().position(|c| c == color).unwrap())
+                        .collect()
+                })
+                .collect()
+        };
+        let vert_colors = colors(&ast.vert_clues);
+        let horz_colors = colors(&ast.horz_clues);
+        let mut result = Grid::colored(w, h, palette.clone());
         if let Some(grid) = ast.grid {
             for (i, grid_line) in grid.0.iter().enumerate() {
                 if w != grid_line.0.len() {
@@ -445,54 +1063,115 @@ impl<'a> Puzzle<'a> {
                     grid.0.len()
                 ));
             }
-            let mut filled = FixedBitSet::with_capacity(w * h);
-            let mut crossed = FixedBitSet::with_capacity(w * h);
-            let mut i = 0;
-            for grid_line in grid.0 {
-                for cell in grid_line.0 {
-                    match cell {
-                        Cell::Filled => {
-                            filled.put(i);
+            if !is_colored {
+                let mut filled = FixedBitSet::with_capacity(w * h);
+                let mut crossed = FixedBitSet::with_capacity(w * h);
+                let mut masked = FixedBitSet::with_capacity(w * h);
+                let mut i = 0;
+                for grid_line in grid.0 {
+                    for cell in grid_line.0 {
+                        match cell {
+                            Cell::Filled => {
+                                filled.put(i);
+                            }
+                            Cell::Crossed => {
+                                crossed.put(i);
+                            }
+                            Cell::Impossible => {
+                                filled.put(i);
+                                crossed.put(i);
+                            }
+                            Cell::Masked => {
+                                crossed.put(i);
+                                masked.put(i);
+                            }
+                            Cell::Colored(color) => {
+                                return Err(format!(
+                                    "color {} used in the grid of a monochrome puzzle",
+                                    color
+                                ));
+                            }
+                            _ => {}
                         }
-                        Cell::Crossed => {
-                            crossed.put(i);
-                        }
-                        Cell::Impossible => {
-                            filled.put(i);
-                            crossed.put(i);
+                        i += 1;
+                    }
+                }
+                result = Grid::with_cells(w, h, filled, crossed);
+                result.masked = masked;
+            } else {
+                for (y, grid_line) in grid.0.iter().enumerate() {
+                    for (x, cell) in grid_line.0.iter().enumerate() {
+                        let color = match cell {
+                            Cell::Filled => DEFAULT_COLOR,
+                            Cell::Colored(color) => *color,
+                            Cell::Crossed => {
+                                result.cross(x, y);
+                                continue;
+                            }
+                            Cell::Impossible => {
+                                result.fill(x, y);
+                                result.cross(x, y);
+                                continue;
+                            }
+                            Cell::Unresolved => {
+                                result.fill(x, y);
+                                continue;
+                            }
+                            Cell::Masked => {
+                                result.mask(x, y);
+                                continue;
+                            }
+                            Cell::Undecided => continue,
+                        };
+                        let index = palette
+                            .iter()
+                            .position(|c| *c == color)
+                            .ok_or_else(|| format!("color {} is not used in any clue", color))?;
+                        result.fill(x, y);
+                        for other in (0..palette.len()).filter(|other| *other != index) {
+                            result.exclude(x, y, other);
                         }
-                        _ => {}
                     }
-                    i += 1;
                 }
+                result.take_changes();
             }
-            Ok(Puzzle {
-                vert_clues: ast.vert_clues,
-                horz_clues: ast.horz_clues,
-                grid: Grid {
-                    width: w,
-                    height: h,
-                    filled,
-                    crossed,
-                },
-            })
-        } else {
-            let filled = FixedBitSet::with_capacity(w * h);
-            let crossed = FixedBitSet::with_capacity(w * h);
-            Ok(Puzzle {
-                vert_clues: ast.vert_clues,
-                horz_clues: ast.horz_clues,
-                grid: Grid {
-                    width: w,
-                    height: h,
-                    filled,
-                    crossed,
-                },
-            })
-        }
-    }
-
-    #[allow(dead_code)]
+        }
+        let mut puzzle = Puzzle {
+            vert_clues: ast.vert_clues,
+            horz_clues: ast.horz_clues,
+            vert_colors,
+            horz_colors,
+            accounting: ClueAccounting::new(w, h),
+            grid: result,
+            journal: vec![],
+            history: vec![],
+            undone: vec![],
+        };
+        puzzle.account(0..h, 0..w);
+        Ok(puzzle)
+    }
+
+    /// Replaces the cells of the puzzle, keeping the cells outside the board.
+    pub fn with_grid(mut self, mut grid: Grid) -> Result<Self, String> {
+        if grid.width != self.width() || grid.height != self.height() || !grid.palette.is_empty() {
+            return Err(format!(
+                "grid does not fit a {} by {} monochrome puzzle",
+                self.width(),
+                self.height()
+            ));
+        }
+        grid.crossed.extend(self.grid.masked.ones());
+        grid.masked = self.grid.masked;
+        grid.take_changes();
+        self.grid = grid;
+        self.journal.clear();
+        self.history.clear();
+        self.undone.clear();
+        let (w, h) = (self.width(), self.height());
+        self.account(0..h, 0..w);
+        Ok(self)
+    }
+
     pub fn into_ast_without_grid(self) -> parser::Puzzle<'a> {
         parser::Puzzle {
             horz_clues: self.horz_clues,
@@ -501,7 +1180,7 @@ impl<'a> Puzzle<'a> {
         }
     }
 
-    pub fn as_ast(&self) -> parser::Puzzle {
+    pub fn as_ast(&self) -> parser::Puzzle<'_> {
         let h = self.horz_clues.0.len();
         let w = self.vert_clues.0.len();
         let mut grid_lines = Vec::with_capacity(w);
@@ -517,6 +1196,81 @@ impl<'a> Puzzle<'a> {
     }
 }
 
+impl<'a> Board for Puzzle<'a> {
+    type Axis = Axis;
+    fn lines(&self, axis: Axis) -> usize {
+        match axis {
+            Axis::Horz => self.height(),
+            Axis::Vert => self.width(),
+        }
+    }
+    fn clue(&self, axis: Axis, line: usize) -> (&[usize], &[usize]) {
+        let (clues, colors) = match axis {
+            Axis::Horz => (&self.horz_clues, &self.horz_colors),
+            Axis::Vert => (&self.vert_clues, &self.vert_colors),
+        };
+        let colors = colors.get(line).map_or(&[][..], |colors| colors);
+        (&clues.0[line].numbers, colors)
+    }
+    fn is_colored(&self) -> bool {
+        Puzzle::is_colored(self)
+    }
+    fn is_finished(&self, axis: Axis, line: usize) -> bool {
+        Puzzle::is_finished(self, axis, line)
+    }
+    fn with_line<R, F: FnOnce(&dyn Line) -> R>(&self, axis: Axis, line: usize, f: F) -> R {
+        match axis {
+            Axis::Horz => f(&HorzLine {
+                grid: &self.grid,
+                y: line,
+            }),
+            Axis::Vert => f(&VertLine {
+                grid: &self.grid,
+                x: line,
+            }),
+        }
+    }
+    /// Records the cells the hint changes as one step of the history.
+    fn apply(&mut self, axis: Axis, line: usize, hint: &dyn LineHint) {
+        let start = self.journal.len();
+        match axis {
+            Axis::Horz => hint.apply(&mut HorzLineMut {
+                grid: &mut self.grid,
+                y: line,
+                journal: Some(&mut self.journal),
+            }),
+            Axis::Vert => hint.apply(&mut VertLineMut {
+                grid: &mut self.grid,
+                x: line,
+                journal: Some(&mut self.journal),
+            }),
+        }
+        if self.journal.len() > start {
+            self.history.push(start);
+            self.undone.clear();
+        }
+    }
+    fn take_changed_lines(&mut self) -> Vec<FixedBitSet> {
+        let changes = self.take_changes();
+        vec![changes.horz, changes.vert]
+    }
+    fn is_complete(&self) -> bool {
+        Puzzle::is_complete(self)
+    }
+    fn decided(&self) -> usize {
+        Puzzle::decided(self)
+    }
+    fn lookahead(
+        &self,
+        pass: &LookaheadPass,
+        passes: &[Pass],
+        axis: Axis,
+        lines: &FixedBitSet,
+    ) -> Vec<Hint<Lookahead>> {
+        pass.run_puzzle(passes, &axis, self, lines)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum Theme {
     Ascii,
@@ -561,6 +1315,7 @@ impl Theme {
         View {
             puzzle,
             theme: self,
+            satisfied: Satisfied::Plain,
         }
     }
 }
@@ -577,9 +1332,58 @@ impl FromStr for Theme {
     }
 }
 
+/// How views show the numbers of clues that are known to be placed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Satisfied {
+    Plain,
+    /// Shown faint, using terminal escape codes.
+    Dim,
+    /// Shown struck out, using terminal escape codes.
+    Strike,
+}
+
+impl Satisfied {
+    fn style(&self, entry: String) -> String {
+        match self {
+            Satisfied::Plain => entry,
+            Satisfied::Dim => format!("\x1b[2m{}\x1b[0m", entry),
+            Satisfied::Strike => format!("\x1b[9m{}\x1b[0m", entry),
+        }
+    }
+}
+
+impl FromStr for Satisfied {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Satisfied::Plain),
+            "dim" => Ok(Satisfied::Dim),
+            "strike" => Ok(Satisfied::Strike),
+            _ => Err("unrecognized style"),
+        }
+    }
+}
+
 pub struct View<'a> {
     puzzle: &'a Puzzle<'a>,
     theme: &'a Theme,
+    satisfied: Satisfied,
+}
+
+impl<'a> View<'a> {
+    /// Shows the placed numbers of each clue in the given style.
+    pub fn with_satisfied(self, satisfied: Satisfied) -> Self {
+        View { satisfied, ..self }
+    }
+
+    /// Styles a padded clue entry if its number is placed.
+    fn styled(&self, axis: Axis, line: usize, i: usize, entry: String) -> String {
+        if self.puzzle.satisfied(axis, line).contains(i) {
+            self.satisfied.style(entry)
+        } else {
+            entry
+        }
+    }
 }
 
 impl<'a> fmt::Display for View<'a> {
@@ -593,9 +1397,11 @@ impl<'a> fmt::Display for View<'a> {
         let max_horz_clue_len = self.puzzle.max_horz_clue_len();
         for i in 0..max_vert_clue_len {
             write!(f, "{: >width$}", "", width = 3 * max_horz_clue_len)?;
-            for clue in &self.puzzle.vert_clues.0 {
-                if clue.0.len() > max_vert_clue_len - i - 1 {
-                    write!(f, "{: >2}", clue.0[clue.0.len() - (max_vert_clue_len - i)])?;
+            for (x, clue) in self.puzzle.vert_clues.0.iter().enumerate() {
+                if clue.numbers.len() > max_vert_clue_len - i - 1 {
+                    let k = clue.numbers.len() - (max_vert_clue_len - i);
+                    let entry = format!("{: >2}", clue_entry(clue, k));
+                    write!(f, "{}", self.styled(Axis::Vert, x, k, entry))?;
                 } else {
                     write!(f, "  ")?;
                 }
@@ -604,8 +1410,10 @@ impl<'a> fmt::Display for View<'a> {
         }
         for (y, clue) in self.puzzle.horz_clues.0.iter().enumerate() {
             for i in 0..max_horz_clue_len {
-                if clue.0.len() > max_horz_clue_len - i - 1 {
-                    write!(f, " {: >2}", clue.0[clue.0.len() - (max_horz_clue_len - i)])?;
+                if clue.numbers.len() > max_horz_clue_len - i - 1 {
+                    let k = clue.numbers.len() - (max_horz_clue_len - i);
+                    let entry = format!("{: >2}", clue_entry(clue, k));
+                    write!(f, " {}", self.styled(Axis::Horz, y, k, entry))?;
                 } else {
                     write!(f, "   ")?;
                 }
@@ -616,6 +1424,9 @@ impl<'a> fmt::Display for View<'a> {
                     Cell::Filled => self.theme.filled(),
                     Cell::Impossible => self.theme.impossible(),
                     Cell::Undecided => self.theme.undecided(),
+                    Cell::Colored(color) => color,
+                    Cell::Unresolved => '?',
+                    Cell::Masked => ' ',
                 };
                 write!(f, " {}", ch)?;
             }
@@ -625,23 +1436,185 @@ impl<'a> fmt::Display for View<'a> {
     }
 }
 
-pub fn line_grid(s: &str) -> Grid {
-    use parser::NonoParser;
-    use parser::Rule;
-    use pest::Parser;
-    let s = format!("[{}||{}]", ";".repeat(s.len() - 1), s);
-    let ast = NonoParser::parse(Rule::puzzle, &s)
-        .unwrap_or_else(|e| panic!("{}", e))
-        .next()
-        .map(parser::Puzzle::from)
-        .unwrap();
-    Puzzle::try_from_ast(ast).unwrap().grid
+fn line_is_solved(line: &dyn Line, clue: &Clue) -> bool {
+    if line.colors() == 0 {
+        return line.filled_runs() == clue.numbers;
+    }
+    let expected: Vec<(usize, char)> = clue
+        .numbers
+        .iter()
+        .cloned()
+        .zip(clue.colors.iter().cloned())
+        .collect();
+    line.colored_runs() == expected
+}
+
+/// A number of a clue as shown in views, with its color letter unless it has
+/// the default color.
+fn clue_entry(clue: &Clue, i: usize) -> String {
+    if clue.colors[i] == DEFAULT_COLOR {
+        clue.numbers[i].to_string()
+    } else {
+        format!("{}{}", clue.numbers[i], clue.colors[i])
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use solver;
+
+    #[test]
+    fn changes() {
+        let mut grid = Grid::new(4, 3);
+        assert!(grid.horz_mut(1).fill(2));
+        assert!(!grid.horz_mut(1).fill(2));
+        assert!(grid.horz_mut(2).cross(0));
+        let changes = grid.take_changes();
+        assert_eq!(changes.horz.ones().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(changes.vert.ones().collect::<Vec<_>>(), vec![0, 2]);
+        let changes = grid.take_changes();
+        assert_eq!(changes.horz.ones().count(), 0);
+        assert_eq!(changes.vert.ones().count(), 0);
+    }
+
+    #[test]
+    fn clues_must_fit() {
+        let puzzle = |s: &str| Puzzle::try_from_ast(s.parse().unwrap());
+        assert!(puzzle("[1,1;;1|3;;1]").is_ok());
+        assert!(puzzle("[1,1;;1|4;;]").is_err());
+        assert!(puzzle("[1,1;;1|3;1,1,1;]").is_err());
+        assert!(puzzle("[1,2;;|1;;]").is_err());
+        assert!(puzzle("[99999999999999999999999;;|;;]").is_err());
+        assert!(puzzle("[1;;1|1,0,1]").is_err());
+        assert!(puzzle("[0;|;]").is_err());
+    }
+
+    #[test]
+    fn colored_puzzles() {
+        let puzzle = |s: &str| Puzzle::try_from_ast(s.parse().unwrap());
+        assert!(puzzle("[;|1r,1b]").is_ok());
+        assert!(puzzle("[;|1r,1r]").is_err());
+        assert!(puzzle("[1r;1b|1r,1b|g.]").is_err());
+        assert!(puzzle("[1;1|2|r.]").is_err());
+
+        let solved = puzzle("[1r;1b|1r,1b|rb]").unwrap();
+        assert!(solved.is_colored());
+        assert!(solved.is_solved());
+        let swapped = puzzle("[1r;1b|1r,1b|br]").unwrap();
+        assert!(swapped.is_complete());
+        assert!(!swapped.is_solved());
+        let unresolved = puzzle("[1r;1b|1r,1b|r?]").unwrap();
+        assert!(!unresolved.is_complete());
+        assert_eq!(format!("{}", unresolved.as_ast()), "[1r;1b|1r,1b|r?]");
+    }
+
+    #[test]
+    fn masks() {
+        let puzzle = Puzzle::try_from_ast("[1;;1|1;;1|#_.;___;.x_]".parse().unwrap()).unwrap();
+        assert_eq!(puzzle.grid.get(1, 0), Cell::Masked);
+        assert!(puzzle.grid.is_crossed(1, 1));
+        assert_eq!(format!("{}", puzzle.as_ast()), "[1;;1|1;;1|#_.;___;.x_]");
+        let view = format!("{}", Theme::Unicode.view(&puzzle));
+        assert_eq!(view.lines().nth(3), Some("  1 · ⨉  "));
+    }
+
+    #[test]
+    fn clue_accounting() {
+        let mut puzzle = Puzzle::try_from_ast("[1;1|1;1|#x;..]".parse().unwrap()).unwrap();
+        assert_eq!(
+            puzzle.satisfied(Axis::Horz, 0).ones().collect::<Vec<_>>(),
+            [0]
+        );
+        assert!(puzzle.is_finished(Axis::Horz, 0));
+        assert_eq!(puzzle.satisfied(Axis::Vert, 0).ones().count(), 1);
+        assert!(!puzzle.is_finished(Axis::Vert, 0));
+        assert_eq!(puzzle.satisfied(Axis::Vert, 1).ones().count(), 0);
+        let view = format!(
+            "{}",
+            Theme::Ascii.view(&puzzle).with_satisfied(Satisfied::Dim)
+        );
+        assert_eq!(view.lines().next(), Some("   \x1b[2m 1\x1b[0m 1"));
+
+        puzzle.grid.cross(0, 1);
+        puzzle.grid.fill(1, 1);
+        puzzle.take_changes();
+        assert!(puzzle.is_finished(Axis::Vert, 1));
+        assert!(puzzle.is_finished(Axis::Horz, 1));
+    }
+
+    #[test]
+    fn undo_redo() {
+        let ast: parser::Puzzle = "[2;1|1;2]".parse().unwrap();
+        let mut puzzle = Puzzle::try_from_ast(ast).unwrap();
+        assert!(!puzzle.undo());
+        let empty = puzzle.snapshot();
+        let passes = solver::Pass::all();
+        let schedule = solver::Strategy::Restart.schedule(&passes, &puzzle);
+        solver::run(&mut puzzle, &passes, schedule, None, 1);
+        assert!(puzzle.is_solved());
+        let solved = format!("{}", puzzle.as_ast());
+        let mut undone = 0;
+        while puzzle.undo() {
+            undone += 1;
+        }
+        assert!(undone > 1);
+        assert_eq!(puzzle.decided(), 0);
+        assert!(!puzzle.is_finished(Axis::Horz, 0));
+        let changes = puzzle.take_changes();
+        assert_eq!(changes.horz.count_ones(..), 2);
+        assert!(puzzle.redo());
+        assert!(puzzle.decided() > 0);
+        let partial = puzzle.snapshot();
+        while puzzle.redo() {}
+        assert_eq!(format!("{}", puzzle.as_ast()), solved);
+        assert!(puzzle.is_finished(Axis::Horz, 0));
+
+        puzzle.restore(partial);
+        assert!(!puzzle.redo());
+        assert!(puzzle.undo());
+        assert!(!puzzle.undo());
+        puzzle.restore(empty);
+        assert_eq!(puzzle.decided(), 0);
+    }
+
+    #[test]
+    fn undo_keeps_direct_edits() {
+        use pass::CompleteLine;
+        let ast: parser::Puzzle = "[1;1|1;1]".parse().unwrap();
+        let mut puzzle = Puzzle::try_from_ast(ast).unwrap();
+        puzzle.grid.fill(0, 0);
+        Hint::new(Axis::Horz, 0, CompleteLine).apply(&mut puzzle);
+        puzzle.grid.fill(1, 1);
+        Hint::new(Axis::Horz, 1, CompleteLine).apply(&mut puzzle);
+        assert!(puzzle.is_solved());
+        assert!(puzzle.undo());
+        assert_eq!(puzzle.decided(), 3);
+        assert!(puzzle.undo());
+        assert_eq!(puzzle.decided(), 2);
+        assert!(!puzzle.undo());
+        // a bit set since the hint was undone is not the hint's to take back
+        puzzle.grid.cross(1, 0);
+        assert!(puzzle.redo());
+        assert!(puzzle.undo());
+        assert_eq!(puzzle.cell(1, 0), Cell::Crossed);
+        assert_eq!(puzzle.decided(), 3);
+    }
+
+    #[test]
+    fn colored_clue_accounting() {
+        let puzzle = Puzzle::try_from_ast("[1r;1r;1b;;1b|2r,1b|rrb.b]".parse().unwrap()).unwrap();
+        assert_eq!(
+            puzzle.satisfied(Axis::Horz, 0).ones().collect::<Vec<_>>(),
+            [0]
+        );
+        let puzzle = Puzzle::try_from_ast("[1r;1r;1b;|2r,1b|rrbx]".parse().unwrap()).unwrap();
+        assert!(puzzle.is_finished(Axis::Horz, 0));
+        let puzzle = Puzzle::try_from_ast("[1r;1r;1b;|2r,1b|rbbx]".parse().unwrap()).unwrap();
+        assert_eq!(puzzle.satisfied(Axis::Horz, 0).ones().count(), 0);
+    }
+
     #[test]
     fn bump_start_empty() {
         let mut grid = Grid::new(10, 1);
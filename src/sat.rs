@@ -0,0 +1,501 @@
+//! Encoding of monochrome puzzles as boolean satisfiability problems.
+//!
+//! Cell `(x, y)` of a puzzle of width `w` is variable `y * w + x + 1`, true
+//! when the cell is filled. Every number of every clue gets one more variable
+//! per cell it could start at. Clauses say that each number starts exactly
+//! once, after the previous number with a gap, and that a cell is filled
+//! exactly when some number covers it. Decided cells of the grid become unit
+//! clauses.
+//!
+//! The encoding can be written out in DIMACS CNF for external solvers, or
+//! solved with the small CDCL solver in this module.
+
+use std::fmt;
+use std::mem;
+
+use parser;
+use parser::Cell;
+use puzzle::Grid;
+
+/// A formula in conjunctive normal form along with the size of the puzzle it
+/// encodes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cnf {
+    width: usize,
+    height: usize,
+    vars: usize,
+    clauses: Vec<Vec<i32>>,
+}
+
+impl Cnf {
+    pub fn encode(ast: &parser::Puzzle) -> Result<Cnf, String> {
+        let width = ast.vert_clues.0.len();
+        let height = ast.horz_clues.0.len();
+        if ast
+            .vert_clues
+            .0
+            .iter()
+            .chain(&ast.horz_clues.0)
+            .any(parser::Clue::is_colored)
+        {
+            return Err("only monochrome puzzles can be encoded".to_string());
+        }
+        if ast
+            .vert_clues
+            .0
+            .iter()
+            .chain(&ast.horz_clues.0)
+            .any(parser::Clue::has_zero)
+        {
+            return Err("clues can not hold a zero".to_string());
+        }
+        let mut cnf = Cnf {
+            width,
+            height,
+            vars: width * height,
+            clauses: vec![],
+        };
+        for (y, clue) in ast.horz_clues.0.iter().enumerate() {
+            let cells: Vec<i32> = (0..width).map(|x| cnf.cell(x, y)).collect();
+            cnf.line(&clue.numbers, &cells);
+        }
+        for (x, clue) in ast.vert_clues.0.iter().enumerate() {
+            let cells: Vec<i32> = (0..height).map(|y| cnf.cell(x, y)).collect();
+            cnf.line(&clue.numbers, &cells);
+        }
+        if let Some(grid) = &ast.grid {
+            if grid.0.len() != height || grid.0.iter().any(|line| line.0.len() != width) {
+                return Err(format!("grid is not {} by {} cells", width, height));
+            }
+            for (y, grid_line) in grid.0.iter().enumerate() {
+                for (x, cell) in grid_line.0.iter().enumerate() {
+                    let var = cnf.cell(x, y);
+                    match cell {
+                        Cell::Filled => cnf.clauses.push(vec![var]),
+                        Cell::Crossed | Cell::Masked => cnf.clauses.push(vec![-var]),
+                        Cell::Impossible => {
+                            cnf.clauses.push(vec![var]);
+                            cnf.clauses.push(vec![-var]);
+                        }
+                        Cell::Undecided => {}
+                        Cell::Colored(_) | Cell::Unresolved => {
+                            return Err(format!(
+                                "cell {} can not be encoded in a monochrome puzzle",
+                                cell
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(cnf)
+    }
+
+    /// The variable of a cell.
+    pub fn cell(&self, x: usize, y: usize) -> i32 {
+        (y * self.width + x + 1) as i32
+    }
+
+    fn var(&mut self) -> i32 {
+        self.vars += 1;
+        self.vars as i32
+    }
+
+    /// Adds the clauses tying the cells of a line to its clue.
+    fn line(&mut self, clue: &[usize], cells: &[i32]) {
+        let len = cells.len();
+        let needed = clue.iter().fold(clue.len().saturating_sub(1), |sum, n| {
+            sum.saturating_add(*n)
+        });
+        if needed > len {
+            // the clue does not fit, which no assignment can fix
+            self.clauses.push(vec![]);
+            return;
+        }
+        // starts[i] holds the first possible start of number i and the
+        // variable for each start from there on
+        let mut starts: Vec<(usize, Vec<i32>)> = vec![];
+        let mut lo = 0;
+        for (i, number) in clue.iter().enumerate() {
+            let after: usize = clue[i + 1..].iter().map(|n| n + 1).sum();
+            let hi = len - after - number;
+            let vars = (lo..=hi).map(|_| self.var()).collect();
+            starts.push((lo, vars));
+            lo += number + 1;
+        }
+        for (i, (lo, vars)) in starts.iter().enumerate() {
+            self.clauses.push(vars.clone());
+            for (a, first) in vars.iter().enumerate() {
+                for second in &vars[a + 1..] {
+                    self.clauses.push(vec![-first, -second]);
+                }
+            }
+            for (offset, var) in vars.iter().enumerate() {
+                let start = lo + offset;
+                for cell in &cells[start..start + clue[i]] {
+                    self.clauses.push(vec![-var, *cell]);
+                }
+                if let Some((next_lo, next_vars)) = starts.get(i + 1) {
+                    let earliest = start + clue[i] + 1;
+                    let mut clause = vec![-var];
+                    clause.extend(
+                        next_vars
+                            .iter()
+                            .enumerate()
+                            .filter(|(offset, _)| next_lo + offset >= earliest)
+                            .map(|(_, next)| *next),
+                    );
+                    self.clauses.push(clause);
+                }
+            }
+        }
+        for (c, cell) in cells.iter().enumerate() {
+            let mut clause = vec![-cell];
+            for (i, (lo, vars)) in starts.iter().enumerate() {
+                clause.extend(
+                    vars.iter()
+                        .enumerate()
+                        .filter(|(offset, _)| lo + offset <= c && c < lo + offset + clue[i])
+                        .map(|(_, var)| *var),
+                );
+            }
+            self.clauses.push(clause);
+        }
+    }
+
+    /// Looks for an assignment that satisfies every clause, returning the
+    /// value of each variable starting from variable 1.
+    pub fn solve(&self) -> Option<Vec<bool>> {
+        Cdcl::new(self)?.solve()
+    }
+
+    /// The grid of cells given by an assignment.
+    pub fn grid(&self, model: &[bool]) -> Grid {
+        let mut grid = Grid::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if model[self.cell(x, y) as usize - 1] {
+                    grid.fill(x, y);
+                } else {
+                    grid.cross(x, y);
+                }
+            }
+        }
+        grid
+    }
+}
+
+impl fmt::Display for Cnf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "c {} by {} cells, cell (x, y) is variable y * {} + x + 1",
+            self.width, self.height, self.width
+        )?;
+        writeln!(f, "p cnf {} {}", self.vars, self.clauses.len())?;
+        for clause in &self.clauses {
+            for literal in clause {
+                write!(f, "{} ", literal)?;
+            }
+            writeln!(f, "0")?;
+        }
+        Ok(())
+    }
+}
+
+/// Index of a literal in the watch lists.
+fn index(literal: i32) -> usize {
+    2 * var(literal) + (literal < 0) as usize
+}
+
+/// Index of the variable of a literal, counting from zero.
+fn var(literal: i32) -> usize {
+    literal.unsigned_abs() as usize - 1
+}
+
+/// A conflict-driven clause learning search, with unit propagation over two
+/// watched literals per clause.
+///
+/// The literal a clause implies is kept first in the clause.
+struct Cdcl {
+    clauses: Vec<Vec<i32>>,
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    /// The decision level each variable was assigned at.
+    levels: Vec<usize>,
+    /// The clause that implied each variable, if it was not decided.
+    reasons: Vec<Option<usize>>,
+    trail: Vec<i32>,
+    /// The length of the trail before each decision.
+    decisions: Vec<usize>,
+    propagated: usize,
+    activity: Vec<f64>,
+    bump: f64,
+    /// The last value of each variable, tried first when deciding it again.
+    phases: Vec<bool>,
+}
+
+impl Cdcl {
+    /// Sets up the search, or returns `None` if the clauses contradict each
+    /// other outright.
+    fn new(cnf: &Cnf) -> Option<Cdcl> {
+        let mut cdcl = Cdcl {
+            clauses: vec![],
+            watches: vec![vec![]; 2 * cnf.vars],
+            values: vec![None; cnf.vars],
+            levels: vec![0; cnf.vars],
+            reasons: vec![None; cnf.vars],
+            trail: vec![],
+            decisions: vec![],
+            propagated: 0,
+            activity: vec![0.0; cnf.vars],
+            bump: 1.0,
+            phases: vec![false; cnf.vars],
+        };
+        for clause in &cnf.clauses {
+            match clause.len() {
+                0 => return None,
+                1 => {
+                    if !cdcl.assign(clause[0], None) {
+                        return None;
+                    }
+                }
+                _ => {
+                    cdcl.add(clause.clone());
+                }
+            }
+        }
+        Some(cdcl)
+    }
+
+    fn add(&mut self, clause: Vec<i32>) -> usize {
+        let c = self.clauses.len();
+        self.watches[index(clause[0])].push(c);
+        self.watches[index(clause[1])].push(c);
+        self.clauses.push(clause);
+        c
+    }
+
+    fn value(&self, literal: i32) -> Option<bool> {
+        self.values[var(literal)].map(|value| value == (literal > 0))
+    }
+
+    /// Makes the literal true, returning false if it already is false.
+    fn assign(&mut self, literal: i32, reason: Option<usize>) -> bool {
+        match self.value(literal) {
+            Some(value) => value,
+            None => {
+                let v = var(literal);
+                self.values[v] = Some(literal > 0);
+                self.levels[v] = self.decisions.len();
+                self.reasons[v] = reason;
+                self.trail.push(literal);
+                true
+            }
+        }
+    }
+
+    /// Assigns the literals implied by the trail, returning the clause that
+    /// ends up false if there is one.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let falsified = -self.trail[self.propagated];
+            self.propagated += 1;
+            let mut watching = mem::take(&mut self.watches[index(falsified)]);
+            let mut kept = 0;
+            let mut conflict = None;
+            for w in 0..watching.len() {
+                let c = watching[w];
+                if conflict.is_some() {
+                    watching[kept] = c;
+                    kept += 1;
+                    continue;
+                }
+                let values = &self.values;
+                let clause = &mut self.clauses[c];
+                if clause[0] == falsified {
+                    clause.swap(0, 1);
+                }
+                let other = clause[0];
+                let replacement = (2..clause.len()).find(|k| {
+                    let literal = clause[*k];
+                    values[var(literal)].is_none_or(|value| value == (literal > 0))
+                });
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    let watched = clause[1];
+                    self.watches[index(watched)].push(c);
+                    continue;
+                }
+                watching[kept] = c;
+                kept += 1;
+                if !self.assign(other, Some(c)) {
+                    conflict = Some(c);
+                }
+            }
+            watching.truncate(kept);
+            self.watches[index(falsified)] = watching;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// Learns a clause from a conflict by resolving it with the clauses that
+    /// implied its literals, up to the first literal of the current decision
+    /// level that all of them go through. Returns the clause, with the
+    /// literal it implies first, and the level to jump back to.
+    fn analyze(&mut self, conflict: usize) -> (Vec<i32>, usize) {
+        let level = self.decisions.len();
+        let mut seen = vec![false; self.values.len()];
+        let mut learned = vec![0];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut i = self.trail.len();
+        let uip = loop {
+            for k in 0..self.clauses[clause].len() {
+                let literal = self.clauses[clause][k];
+                let v = var(literal);
+                if seen[v] || self.levels[v] == 0 {
+                    continue;
+                }
+                seen[v] = true;
+                self.activity[v] += self.bump;
+                if self.levels[v] == level {
+                    pending += 1;
+                } else {
+                    learned.push(literal);
+                }
+            }
+            loop {
+                i -= 1;
+                if seen[var(self.trail[i])] {
+                    break;
+                }
+            }
+            let literal = self.trail[i];
+            pending -= 1;
+            if pending == 0 {
+                break literal;
+            }
+            clause = self.reasons[var(literal)].unwrap();
+        };
+        learned[0] = -uip;
+        let mut back = 0;
+        for k in 1..learned.len() {
+            if self.levels[var(learned[k])] > back {
+                back = self.levels[var(learned[k])];
+                learned.swap(1, k);
+            }
+        }
+        self.bump *= 1.05;
+        if self.bump > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
+            }
+            self.bump *= 1e-100;
+        }
+        (learned, back)
+    }
+
+    /// Undoes the assignments made above the given decision level.
+    fn backtrack(&mut self, level: usize) {
+        let len = self.decisions[level];
+        for literal in self.trail.drain(len..) {
+            let v = var(literal);
+            self.values[v] = None;
+            self.reasons[v] = None;
+            self.phases[v] = literal > 0;
+        }
+        self.decisions.truncate(level);
+        self.propagated = len;
+    }
+
+    fn solve(mut self) -> Option<Vec<bool>> {
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.decisions.is_empty() {
+                    return None;
+                }
+                let (learned, level) = self.analyze(conflict);
+                self.backtrack(level);
+                let literal = learned[0];
+                let reason = if learned.len() > 1 {
+                    Some(self.add(learned))
+                } else {
+                    None
+                };
+                self.assign(literal, reason);
+                continue;
+            }
+            let unassigned = (0..self.values.len()).filter(|v| self.values[*v].is_none());
+            match unassigned.max_by(|a, b| self.activity[*a].total_cmp(&self.activity[*b])) {
+                Some(v) => {
+                    let literal = if self.phases[v] {
+                        v as i32 + 1
+                    } else {
+                        -(v as i32 + 1)
+                    };
+                    self.decisions.push(self.trail.len());
+                    self.assign(literal, None);
+                }
+                None => {
+                    return Some(self.values.iter().map(|value| value.unwrap()).collect());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use puzzle::Puzzle;
+
+    fn solve(s: &str) -> Option<String> {
+        let ast: parser::Puzzle = s.parse().unwrap();
+        let cnf = Cnf::encode(&ast).unwrap();
+        let model = cnf.solve()?;
+        let puzzle = Puzzle::try_from_ast(ast).unwrap();
+        let puzzle = puzzle.with_grid(cnf.grid(&model)).unwrap();
+        assert!(puzzle.is_solved());
+        Some(format!("{}", puzzle.as_ast()))
+    }
+
+    #[test]
+    fn dimacs() {
+        let ast: parser::Puzzle = "[1;|1;]".parse().unwrap();
+        let cnf = Cnf::encode(&ast).unwrap();
+        let dimacs = format!("{}", cnf);
+        let mut lines = dimacs.lines().skip(1);
+        assert_eq!(lines.next(), Some("p cnf 8 16"));
+        assert!(lines.all(|line| line.ends_with(" 0")));
+    }
+
+    #[test]
+    fn solutions() {
+        assert_eq!(
+            solve("[1,1,2;1,2;6;1|3;2;1,1;2;3;1,1]"),
+            Some("[1,1,2;1,2;6;1|3;2;1,1;2;3;1,1|###x;xx##;#x#x;x##x;###x;#x#x]".to_string())
+        );
+        assert_eq!(
+            solve("[1;3;4;1,1;1,2,1;3,1;3|1,2;2,2;1,2;1,3;1,1;1,1;3]"),
+            Some("[1;3;4;1,1;1,2,1;3,1;3|1,2;2,2;1,2;1,3;1,1;1,1;3|x#x##xx;##xxx##;x#xxx##;xx#x###;xx#x#xx;xx#xx#x;xx###xx]".to_string())
+        );
+        assert_eq!(
+            solve("[1;1|1;1|._;..]"),
+            Some("[1;1|1;1|#_;x#]".to_string())
+        );
+    }
+
+    #[test]
+    fn no_solution() {
+        assert_eq!(solve("[1;1|1;1|#.;#.]"), None);
+        assert_eq!(solve("[2;|;2]"), None);
+        assert_eq!(solve("[3;|1;1]"), None);
+        assert!(Cnf::encode(&"[1r|1r]".parse().unwrap()).is_err());
+        assert!(Cnf::encode(&"[1;;1|1,0,1]".parse().unwrap()).is_err());
+    }
+}
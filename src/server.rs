@@ -0,0 +1,490 @@
+use pest::Parser;
+use serde_json;
+use std::borrow::Cow;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::thread;
+use std::time::Duration;
+
+use parser;
+use parser::Clue;
+use parser::ClueList;
+use parser::GridLine;
+use parser::NonoParser;
+use parser::Rule;
+use puzzle;
+use solver::Pass;
+use solver::Steps;
+use solver::Strategy;
+
+/// The largest request body the server reads, in bytes.
+const MAX_BODY: usize = 1 << 20;
+
+/// The longest request or header line the server reads, in bytes.
+const MAX_LINE: usize = 8 << 10;
+
+/// The most header lines the server reads.
+const MAX_HEADERS: usize = 100;
+
+/// How long the server waits for a client to send or receive more data.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+struct JsonPuzzle {
+    vert_clues: Vec<Vec<usize>>,
+    horz_clues: Vec<Vec<usize>>,
+    grid: Option<Vec<String>>,
+}
+
+impl JsonPuzzle {
+    fn into_ast(self) -> Result<parser::Puzzle<'static>, String> {
+        let grid = match self.grid {
+            Some(lines) => {
+                let mut grid_lines = Vec::with_capacity(lines.len());
+                for line in lines {
+                    let pair = NonoParser::parse(Rule::grid_line, &line)
+                        .map_err(|e| format!("{}", e))?
+                        .next()
+                        .unwrap();
+                    if pair.as_str().len() != line.len() {
+                        return Err(format!("invalid grid line: {}", line));
+                    }
+                    grid_lines.push(GridLine::from(pair));
+                }
+                Some(parser::Grid(grid_lines))
+            }
+            None => None,
+        };
+        // whether the clues fit is left to `Puzzle::try_from_ast`
+        if self.vert_clues.is_empty() || self.horz_clues.is_empty() {
+            return Err("clue lists must not be empty".to_string());
+        }
+        Ok(parser::Puzzle {
+            vert_clues: Cow::Owned(ClueList(
+                self.vert_clues.into_iter().map(Clue::from).collect(),
+            )),
+            horz_clues: Cow::Owned(ClueList(
+                self.horz_clues.into_iter().map(Clue::from).collect(),
+            )),
+            grid,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Step {
+    pass: String,
+    axis: String,
+    hints: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct HintResponse {
+    puzzle: String,
+    step: Option<Step>,
+}
+
+#[derive(Debug, Serialize)]
+struct SolveResponse {
+    puzzle: String,
+    complete: bool,
+    passes: usize,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Serialize)]
+struct CheckResponse {
+    complete: bool,
+    solved: bool,
+    contradiction: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: String,
+}
+
+struct Response {
+    status: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn ok<T: ::serde::Serialize>(value: &T) -> Self {
+        Response {
+            status: "200 OK",
+            body: serde_json::to_string(value).unwrap(),
+        }
+    }
+
+    fn error(status: &'static str, message: String) -> Self {
+        Response {
+            status,
+            body: serde_json::to_string(&ErrorResponse { error: message }).unwrap(),
+        }
+    }
+}
+
+fn parse_body(body: &str) -> Result<parser::Puzzle<'static>, String> {
+    let body = body.trim();
+    if body.starts_with('{') {
+        serde_json::from_str::<JsonPuzzle>(body)
+            .map_err(|e| format!("{}", e))?
+            .into_ast()
+    } else {
+        body.parse()
+    }
+}
+
+/// Runs passes on the puzzle until it is complete or the passes are exhausted.
+///
+/// When `limit` is given, stops after that many passes have produced hints.
+fn run(
+    puzzle: &mut puzzle::Puzzle,
+    passes: &[Pass],
+    strategy: Strategy,
+    limit: Option<usize>,
+) -> (usize, Vec<Step>) {
+    let schedule = strategy.schedule(passes, puzzle);
+    let mut solve = Steps::new(puzzle, passes, schedule, None, 1);
+    let mut steps = vec![];
+    while limit.is_none_or(|limit| steps.len() < limit) {
+        let step = match solve.next() {
+            Some(step) => step,
+            None => break,
+        };
+        if !step.hints.is_empty() {
+            steps.push(Step {
+                pass: format!("{:?}", step.pass),
+                axis: format!("{:?}", step.axis),
+                hints: step
+                    .hints
+                    .iter()
+                    .map(|hint| format!("{:?}", hint))
+                    .collect(),
+            });
+        }
+    }
+    (solve.rounds(), steps)
+}
+
+fn handle_puzzle(
+    path: &str,
+    body: &str,
+    passes: &[Pass],
+    strategy: Strategy,
+) -> Result<Response, String> {
+    let ast = parse_body(body)?;
+    let mut puzzle = puzzle::Puzzle::try_from_ast(ast)?;
+    let response = match path {
+        "/hint" => {
+            let (_, mut steps) = run(&mut puzzle, passes, strategy, Some(1));
+            Response::ok(&HintResponse {
+                puzzle: format!("{}", puzzle.as_ast()),
+                step: steps.pop(),
+            })
+        }
+        "/solve" => {
+            let (pass_counter, steps) = run(&mut puzzle, passes, strategy, None);
+            Response::ok(&SolveResponse {
+                puzzle: format!("{}", puzzle.as_ast()),
+                complete: puzzle.is_complete(),
+                passes: pass_counter,
+                steps,
+            })
+        }
+        "/check" => {
+            let complete = puzzle.is_complete();
+            let solved = puzzle.is_solved();
+            run(&mut puzzle, passes, strategy, None);
+            Response::ok(&CheckResponse {
+                complete,
+                solved,
+                contradiction: puzzle.is_contradictory()
+                    || (puzzle.is_complete() && !puzzle.is_solved()),
+            })
+        }
+        _ => unreachable!(),
+    };
+    Ok(response)
+}
+
+fn route(request: &Request, passes: &[Pass], strategy: Strategy) -> Response {
+    match request.path.as_str() {
+        "/hint" | "/solve" | "/check" => {
+            if request.method != "POST" {
+                return Response::error("405 Method Not Allowed", "use POST".to_string());
+            }
+            handle_puzzle(&request.path, &request.body, passes, strategy)
+                .unwrap_or_else(|e| Response::error("400 Bad Request", e))
+        }
+        _ => Response::error(
+            "404 Not Found",
+            format!("no such endpoint: {}", request.path),
+        ),
+    }
+}
+
+fn bad_request<E: ToString>(e: E) -> Response {
+    Response::error("400 Bad Request", e.to_string())
+}
+
+fn read_error(e: io::Error) -> Response {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Response::error(
+            "408 Request Timeout",
+            "the request was not sent in time".to_string(),
+        ),
+        _ => bad_request(e),
+    }
+}
+
+/// Reads a line of at most `MAX_LINE` bytes, answering with `status` if it is
+/// longer.
+fn read_line<R: BufRead>(
+    reader: &mut R,
+    status: &'static str,
+    line: &mut String,
+) -> Result<usize, Response> {
+    let read = reader
+        .take(MAX_LINE as u64 + 1)
+        .read_line(line)
+        .map_err(read_error)?;
+    if read > MAX_LINE {
+        return Err(Response::error(
+            status,
+            format!("lines are limited to {} bytes", MAX_LINE),
+        ));
+    }
+    Ok(read)
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, Response> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    read_line(&mut reader, "414 URI Too Long", &mut request_line)?;
+    let mut words = request_line.split_whitespace();
+    let method = words.next().unwrap_or("").to_string();
+    let path = words.next().unwrap_or("").to_string();
+
+    let mut content_length = 0;
+    let mut headers = 0;
+    loop {
+        let mut header = String::new();
+        let status = "431 Request Header Fields Too Large";
+        if read_line(&mut reader, status, &mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(Response::error(
+                status,
+                format!("requests are limited to {} headers", MAX_HEADERS),
+            ));
+        }
+        if let Some(colon) = header.find(':') {
+            let (name, value) = header.split_at(colon);
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value[1..].trim().parse().map_err(|_| {
+                    bad_request(format!("invalid Content-Length: {}", value[1..].trim()))
+                })?;
+            }
+        }
+    }
+    if content_length > MAX_BODY {
+        return Err(Response::error(
+            "413 Payload Too Large",
+            format!("request bodies are limited to {} bytes", MAX_BODY),
+        ));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(read_error)?;
+    let body = String::from_utf8(body).map_err(bad_request)?;
+
+    Ok(Request { method, path, body })
+}
+
+fn write_response(mut stream: &TcpStream, response: &Response) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+pub fn handle(stream: &TcpStream, passes: &[Pass], strategy: Strategy) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let response = match read_request(stream) {
+        // a bug in a pass must not take the server down with it
+        Ok(request) => panic::catch_unwind(AssertUnwindSafe(|| route(&request, passes, strategy)))
+            .unwrap_or_else(|_| {
+                Response::error(
+                    "500 Internal Server Error",
+                    "the puzzle could not be processed".to_string(),
+                )
+            }),
+        Err(response) => response,
+    };
+    write_response(stream, &response)
+}
+
+/// Answers each connection on a thread of its own, so that a slow client does
+/// not hold up the others.
+pub fn serve(listener: &TcpListener, passes: &[Pass], strategy: Strategy) {
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    scope.spawn(move || {
+                        if let Err(e) = handle(&stream, passes, strategy) {
+                            eprintln!("{}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, body: &str) -> (String, serde_json::Value) {
+        let head = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n",
+            method,
+            path,
+            body.len()
+        );
+        raw_request(&head, body)
+    }
+
+    fn raw_request(head: &str, body: &str) -> (String, serde_json::Value) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let passes = Pass::all();
+            let (stream, _) = listener.accept().unwrap();
+            handle(&stream, &passes, Strategy::Restart).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        write!(client, "{}{}", head, body).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+
+        let (head, body) = response.split_at(response.find("\r\n\r\n").unwrap());
+        let status = head.lines().next().unwrap().to_string();
+        (status, serde_json::from_str(body.trim()).unwrap())
+    }
+
+    #[test]
+    fn solve_one_line() {
+        let (status, body) = request("POST", "/solve", "[2;2|2;2]");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body["puzzle"], "[2;2|2;2|##;##]");
+        assert_eq!(body["complete"], true);
+    }
+
+    #[test]
+    fn hint_json() {
+        let (status, body) = request(
+            "POST",
+            "/hint",
+            r#"{"vert_clues": [[2], [2]], "horz_clues": [[2], [2]]}"#,
+        );
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body["step"]["axis"], "Horz");
+        assert_eq!(body["step"]["hints"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn check() {
+        let (_, body) = request("POST", "/check", "[1;1|1;1|#x;x#]");
+        assert_eq!(body["complete"], true);
+        assert_eq!(body["solved"], true);
+        assert_eq!(body["contradiction"], false);
+
+        let (_, body) = request("POST", "/check", "[1;1|1;1|##;..]");
+        assert_eq!(body["solved"], false);
+        assert_eq!(body["contradiction"], true);
+    }
+
+    #[test]
+    fn errors() {
+        let (status, _) = request("POST", "/solve", "[2;2|2");
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        let (status, _) = request("GET", "/solve", "");
+        assert_eq!(status, "HTTP/1.1 405 Method Not Allowed");
+        let (status, _) = request("POST", "/nope", "");
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        let (status, _) = request(
+            "POST",
+            "/solve",
+            r#"{"vert_clues": [[5]], "horz_clues": [[1]]}"#,
+        );
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+    }
+
+    #[test]
+    fn content_length() {
+        let head = "POST /solve HTTP/1.1\r\nContent-Length: 99999999999999\r\n\r\n";
+        let (status, _) = raw_request(head, "");
+        assert_eq!(status, "HTTP/1.1 413 Payload Too Large");
+        let head = "POST /solve HTTP/1.1\r\nContent-Length: lots\r\n\r\n";
+        let (status, _) = raw_request(head, "[1|1]");
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+    }
+
+    #[test]
+    fn header_limits() {
+        let head = format!(
+            "POST /solve HTTP/1.1\r\nX-Long: {}\r\n\r\n",
+            "a".repeat(MAX_LINE)
+        );
+        let (status, _) = raw_request(&head, "");
+        assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+        let head = format!(
+            "POST /solve HTTP/1.1\r\n{}\r\n",
+            "X-Many: 1\r\n".repeat(MAX_HEADERS + 1)
+        );
+        let (status, _) = raw_request(&head, "");
+        assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+        let head = format!("POST /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        let (status, _) = raw_request(&head, "");
+        assert_eq!(status, "HTTP/1.1 414 URI Too Long");
+    }
+
+    #[test]
+    fn grid_line_garbage() {
+        let (status, body) = request(
+            "POST",
+            "/solve",
+            r#"{"vert_clues": [[1]], "horz_clues": [[1]], "grid": ["x#]"]}"#,
+        );
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert_eq!(body["error"], "invalid grid line: x#]");
+    }
+}
@@ -0,0 +1,1265 @@
+use fixedbitset::FixedBitSet;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
+
+use cache::LineCache;
+use lookahead::Lookahead;
+use lookahead::LookaheadPass;
+use pass::BlockOwnership;
+use pass::BlockOwnershipPass;
+use pass::CompleteLine;
+use pass::CompleteLinePass;
+use pass::ContinuousRangeHint;
+use pass::ContinuousRangePass;
+use pass::CrowdedClue;
+use pass::CrowdedCluePass;
+use pass::DiscreteRangeHint;
+use pass::DiscreteRangePass;
+use pass::EdgeHint;
+use pass::EdgePass;
+use pass::SmallGap;
+use pass::SmallGapPass;
+use puzzle;
+use puzzle::Axes;
+use puzzle::Axis;
+use puzzle::Board;
+use puzzle::Line;
+use puzzle::LineMut;
+use puzzle::LinePassExt;
+
+#[derive(Clone, Debug)]
+pub enum Hint {
+    CrowdedClue(CrowdedClue),
+    CompleteLine(CompleteLine),
+    Edge(EdgeHint),
+    SmallGap(SmallGap),
+    BlockOwnership(BlockOwnership),
+    ContinuousRange(ContinuousRangeHint),
+    DiscreteRange(DiscreteRangeHint),
+    Lookahead(Lookahead),
+}
+
+impl puzzle::LineHint for Hint {
+    fn check(&self, line: &dyn Line) -> bool {
+        match self {
+            Hint::CrowdedClue(inner) => inner.check(line),
+            Hint::CompleteLine(inner) => inner.check(line),
+            Hint::Edge(inner) => inner.check(line),
+            Hint::SmallGap(inner) => inner.check(line),
+            Hint::BlockOwnership(inner) => inner.check(line),
+            Hint::ContinuousRange(inner) => inner.check(line),
+            Hint::DiscreteRange(inner) => inner.check(line),
+            Hint::Lookahead(inner) => inner.check(line),
+        }
+    }
+    fn apply(&self, line: &mut dyn LineMut) {
+        match self {
+            Hint::CrowdedClue(inner) => inner.apply(line),
+            Hint::CompleteLine(inner) => inner.apply(line),
+            Hint::Edge(inner) => inner.apply(line),
+            Hint::SmallGap(inner) => inner.apply(line),
+            Hint::BlockOwnership(inner) => inner.apply(line),
+            Hint::ContinuousRange(inner) => inner.apply(line),
+            Hint::DiscreteRange(inner) => inner.apply(line),
+            Hint::Lookahead(inner) => inner.apply(line),
+        }
+    }
+}
+
+impl Hint {
+    /// Why the hint holds, in words, for the passes that can tell.
+    pub fn reason(&self) -> Option<String> {
+        match self {
+            Hint::SmallGap(inner) => Some(inner.to_string()),
+            Hint::BlockOwnership(inner) => Some(inner.to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Pass {
+    CrowdedClue(CrowdedCluePass),
+    CompleteLine(CompleteLinePass),
+    Edge(EdgePass),
+    SmallGap(SmallGapPass),
+    BlockOwnership(BlockOwnershipPass),
+    ContinuousRange(ContinuousRangePass),
+    DiscreteRange(DiscreteRangePass),
+    Lookahead(LookaheadPass),
+}
+
+impl Pass {
+    /// Passes that only look at the clue and the line length never find
+    /// anything new once they have been run on every line.
+    pub fn is_one_shot(&self) -> bool {
+        match self {
+            Pass::CrowdedClue(_) => true,
+            Pass::CompleteLine(_) => false,
+            Pass::Edge(_) => false,
+            Pass::SmallGap(_) => false,
+            Pass::BlockOwnership(_) => false,
+            Pass::ContinuousRange(_) => false,
+            Pass::DiscreteRange(_) => false,
+            Pass::Lookahead(_) => false,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::CrowdedClue(_) => "crowded",
+            Pass::CompleteLine(_) => "complete",
+            Pass::Edge(_) => "edge",
+            Pass::SmallGap(_) => "gap",
+            Pass::BlockOwnership(_) => "ownership",
+            Pass::ContinuousRange(_) => "continuous",
+            Pass::DiscreteRange(_) => "discrete",
+            Pass::Lookahead(_) => "lookahead",
+        }
+    }
+
+    pub fn all() -> Vec<Pass> {
+        vec![
+            Pass::CrowdedClue(CrowdedCluePass),
+            Pass::CompleteLine(CompleteLinePass),
+            Pass::Edge(EdgePass),
+            Pass::SmallGap(SmallGapPass),
+            Pass::BlockOwnership(BlockOwnershipPass),
+            Pass::ContinuousRange(ContinuousRangePass),
+            Pass::DiscreteRange(DiscreteRangePass),
+        ]
+    }
+}
+
+impl FromStr for Pass {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "crowded" => Ok(Pass::CrowdedClue(CrowdedCluePass)),
+            "complete" => Ok(Pass::CompleteLine(CompleteLinePass)),
+            "edge" => Ok(Pass::Edge(EdgePass)),
+            "gap" => Ok(Pass::SmallGap(SmallGapPass)),
+            "ownership" => Ok(Pass::BlockOwnership(BlockOwnershipPass)),
+            "continuous" => Ok(Pass::ContinuousRange(ContinuousRangePass)),
+            "discrete" => Ok(Pass::DiscreteRange(DiscreteRangePass)),
+            "lookahead" => Ok(Pass::Lookahead(LookaheadPass)),
+            _ => Err("unrecognized pass"),
+        }
+    }
+}
+
+impl puzzle::LinePass for Pass {
+    type Hint = Hint;
+    fn run(&self, clue: &[usize], line: &dyn Line) -> Vec<Box<Self::Hint>> {
+        match self {
+            Pass::CrowdedClue(inner) => inner
+                .run(clue, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::CrowdedClue(*hint)))
+                .collect(),
+            Pass::CompleteLine(inner) => inner
+                .run(clue, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::CompleteLine(*hint)))
+                .collect(),
+            Pass::Edge(inner) => inner
+                .run(clue, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::Edge(*hint)))
+                .collect(),
+            Pass::SmallGap(inner) => inner
+                .run(clue, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::SmallGap(*hint)))
+                .collect(),
+            Pass::BlockOwnership(inner) => inner
+                .run(clue, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::BlockOwnership(*hint)))
+                .collect(),
+            Pass::ContinuousRange(inner) => inner
+                .run(clue, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::ContinuousRange(*hint)))
+                .collect(),
+            Pass::DiscreteRange(inner) => inner
+                .run(clue, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::DiscreteRange(*hint)))
+                .collect(),
+            Pass::Lookahead(inner) => inner
+                .run(clue, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::Lookahead(*hint)))
+                .collect(),
+        }
+    }
+    fn run_colored(
+        &self,
+        clue: &[usize],
+        colors: &[usize],
+        line: &dyn Line,
+    ) -> Vec<Box<Self::Hint>> {
+        match self {
+            Pass::CrowdedClue(inner) => inner
+                .run_colored(clue, colors, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::CrowdedClue(*hint)))
+                .collect(),
+            Pass::CompleteLine(inner) => inner
+                .run_colored(clue, colors, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::CompleteLine(*hint)))
+                .collect(),
+            Pass::Edge(inner) => inner
+                .run_colored(clue, colors, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::Edge(*hint)))
+                .collect(),
+            Pass::SmallGap(inner) => inner
+                .run_colored(clue, colors, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::SmallGap(*hint)))
+                .collect(),
+            Pass::BlockOwnership(inner) => inner
+                .run_colored(clue, colors, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::BlockOwnership(*hint)))
+                .collect(),
+            Pass::ContinuousRange(inner) => inner
+                .run_colored(clue, colors, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::ContinuousRange(*hint)))
+                .collect(),
+            Pass::DiscreteRange(inner) => inner
+                .run_colored(clue, colors, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::DiscreteRange(*hint)))
+                .collect(),
+            Pass::Lookahead(inner) => inner
+                .run_colored(clue, colors, line)
+                .into_iter()
+                .map(|hint| Box::new(Hint::Lookahead(*hint)))
+                .collect(),
+        }
+    }
+}
+
+/// A pass to run on some lines of one axis of the puzzle.
+#[derive(Debug)]
+pub struct Task<'a, A = Axis> {
+    pub pass: &'a Pass,
+    pub axis: A,
+    pub lines: FixedBitSet,
+}
+
+impl<'a, A: Axes> Task<'a, A> {
+    /// Runs the pass and applies the resulting hints to the puzzle. Returns
+    /// the hints along with the lines changed along each axis.
+    ///
+    /// `passes` are the passes of the solve, which lookahead follows its
+    /// assumptions with. If a cache is given, line results are looked up in
+    /// it before running the pass. If `jobs` is more than one, the lines are
+    /// split between that many threads.
+    pub fn apply<B: Board<Axis = A>>(
+        &self,
+        puzzle: &mut B,
+        passes: &[Pass],
+        cache: Option<&LineCache>,
+        jobs: usize,
+    ) -> (Vec<puzzle::Hint<Hint, A>>, Vec<FixedBitSet>) {
+        let hints = match (self.pass, cache) {
+            // lookahead needs the whole puzzle, and its results cannot be
+            // cached by line
+            (Pass::Lookahead(inner), _) => puzzle
+                .lookahead(inner, passes, self.axis, &self.lines)
+                .into_iter()
+                .map(|hint| hint.map(Hint::Lookahead))
+                .collect(),
+            (_, Some(cache)) => {
+                cache
+                    .wrap(self.pass)
+                    .run_puzzle_parallel(&self.axis, puzzle, &self.lines, jobs)
+            }
+            (_, None) => self
+                .pass
+                .run_puzzle_parallel(&self.axis, puzzle, &self.lines, jobs),
+        };
+        for hint in &hints {
+            hint.apply(puzzle);
+        }
+        (hints, puzzle.take_changed_lines())
+    }
+}
+
+/// Decides which task to run next based on the outcome of the previous one.
+///
+/// `changes` holds the lines changed along each axis, in the order of
+/// `Axes::all`.
+pub trait Schedule<'a, A = Axis> {
+    fn initial(&mut self) -> Option<Task<'a, A>>;
+    fn succeeded(&mut self, changes: &[FixedBitSet]) -> Option<Task<'a, A>>;
+    fn failed(&mut self) -> Option<Task<'a, A>>;
+}
+
+/// Lines that have changed since each pass last looked at them, per axis.
+///
+/// A pass finds nothing new on a line it has already been run on unless the
+/// line has changed since, so there is no point in running it again.
+struct DirtyLines {
+    lines: Vec<Vec<FixedBitSet>>,
+}
+
+impl DirtyLines {
+    /// `lines` holds the number of lines along each axis.
+    fn new(passes: &[Pass], lines: &[usize]) -> Self {
+        let lines = passes
+            .iter()
+            .map(|_| {
+                lines
+                    .iter()
+                    .map(|len| {
+                        let mut dirty = FixedBitSet::with_capacity(*len);
+                        dirty.insert_range(..);
+                        dirty
+                    })
+                    .collect()
+            })
+            .collect();
+        DirtyLines { lines }
+    }
+
+    fn is_dirty(&self, p: usize, a: usize) -> bool {
+        self.lines[p][a].ones().next().is_some()
+    }
+
+    fn take(&mut self, p: usize, a: usize) -> FixedBitSet {
+        let lines = self.lines[p][a].clone();
+        self.lines[p][a].clear();
+        lines
+    }
+
+    fn mark(&mut self, passes: &[Pass], changes: &[FixedBitSet]) {
+        let changed = changes.iter().any(|lines| lines.ones().next().is_some());
+        for (pass, lines) in passes.iter().zip(self.lines.iter_mut()) {
+            match pass {
+                // lookahead looks at the whole puzzle from every line
+                Pass::Lookahead(_) if changed => {
+                    for lines in lines.iter_mut() {
+                        lines.insert_range(..);
+                    }
+                }
+                _ if !pass.is_one_shot() => {
+                    for (lines, changes) in lines.iter_mut().zip(changes) {
+                        lines.extend(changes.ones());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Runs the cheapest pass until it fails on every axis, then moves on to the
+/// next pass. Restarts from the cheapest pass whenever a pass makes progress.
+pub struct Restart<'a, A: 'static = Axis> {
+    cur_p: usize,
+    cur_a: usize,
+    fail_count: usize,
+    done: Vec<bool>,
+    dirty: DirtyLines,
+    passes: &'a [Pass],
+    axes: &'static [A],
+}
+
+impl<'a, A: Axes> Restart<'a, A> {
+    /// `lines` holds the number of lines along each axis.
+    pub fn new(passes: &'a [Pass], lines: &[usize]) -> Self {
+        Restart {
+            cur_p: 0,
+            cur_a: 0,
+            fail_count: 0,
+            done: vec![false; passes.len()],
+            dirty: DirtyLines::new(passes, lines),
+            passes,
+            axes: A::all(),
+        }
+    }
+
+    fn next(&mut self, last_p: usize) -> Option<Task<'a, A>> {
+        if self.fail_count >= self.axes.len() {
+            self.cur_p += 1;
+            self.fail_count = 0;
+        }
+
+        self.cur_a = (self.cur_a + 1) % self.axes.len();
+        if self.cur_a == 0 && self.passes[last_p].is_one_shot() {
+            self.done[last_p] = true;
+        }
+        // a pass that is up next has not failed on any axis yet
+        while self.cur_p < self.passes.len() && self.done[self.cur_p] {
+            self.cur_p += 1;
+            self.fail_count = 0;
+        }
+
+        self.task()
+    }
+
+    fn task(&mut self) -> Option<Task<'a, A>> {
+        let pass = self.passes.get(self.cur_p)?;
+        Some(Task {
+            pass,
+            axis: self.axes[self.cur_a],
+            lines: self.dirty.take(self.cur_p, self.cur_a),
+        })
+    }
+}
+
+impl<'a, A: Axes> Schedule<'a, A> for Restart<'a, A> {
+    fn initial(&mut self) -> Option<Task<'a, A>> {
+        self.task()
+    }
+
+    fn succeeded(&mut self, changes: &[FixedBitSet]) -> Option<Task<'a, A>> {
+        self.dirty.mark(self.passes, changes);
+        self.fail_count = 0;
+
+        let last_p = self.cur_p;
+        self.cur_p = 0;
+        self.next(last_p)
+    }
+
+    fn failed(&mut self) -> Option<Task<'a, A>> {
+        self.fail_count += 1;
+
+        let last_p = self.cur_p;
+        self.next(last_p)
+    }
+}
+
+/// Runs every pass on every axis in turn until a full round makes no
+/// progress. One-shot passes drop out of the rotation after one round.
+pub struct RoundRobin<'a, A: 'static = Axis> {
+    cur: usize,
+    idle: usize,
+    done: Vec<bool>,
+    dirty: DirtyLines,
+    passes: &'a [Pass],
+    axes: &'static [A],
+}
+
+impl<'a, A: Axes> RoundRobin<'a, A> {
+    /// `lines` holds the number of lines along each axis.
+    pub fn new(passes: &'a [Pass], lines: &[usize]) -> Self {
+        RoundRobin {
+            cur: 0,
+            idle: 0,
+            done: vec![false; passes.len()],
+            dirty: DirtyLines::new(passes, lines),
+            passes,
+            axes: A::all(),
+        }
+    }
+
+    fn next(&mut self) -> Option<Task<'a, A>> {
+        let n = self.axes.len();
+        let last_p = self.cur / n;
+        if self.cur % n == n - 1 && self.passes[last_p].is_one_shot() {
+            self.done[last_p] = true;
+        }
+
+        let active = self.done.iter().filter(|done| !**done).count();
+        if self.idle >= n * active {
+            return None;
+        }
+
+        loop {
+            self.cur = (self.cur + 1) % (n * self.passes.len());
+            if !self.done[self.cur / n] {
+                return self.task();
+            }
+        }
+    }
+
+    fn task(&mut self) -> Option<Task<'a, A>> {
+        let n = self.axes.len();
+        let pass = self.passes.get(self.cur / n)?;
+        Some(Task {
+            pass,
+            axis: self.axes[self.cur % n],
+            lines: self.dirty.take(self.cur / n, self.cur % n),
+        })
+    }
+}
+
+impl<'a, A: Axes> Schedule<'a, A> for RoundRobin<'a, A> {
+    fn initial(&mut self) -> Option<Task<'a, A>> {
+        self.task()
+    }
+
+    fn succeeded(&mut self, changes: &[FixedBitSet]) -> Option<Task<'a, A>> {
+        self.dirty.mark(self.passes, changes);
+        self.idle = 0;
+        self.next()
+    }
+
+    fn failed(&mut self) -> Option<Task<'a, A>> {
+        if !self.passes[self.cur / self.axes.len()].is_one_shot() {
+            self.idle += 1;
+        }
+        self.next()
+    }
+}
+
+/// Runs the cheapest pass that has changed lines to look at, on those lines
+/// only, until no pass has anything left to look at.
+pub struct DirtyQueue<'a, A: 'static = Axis> {
+    dirty: DirtyLines,
+    passes: &'a [Pass],
+    axes: &'static [A],
+}
+
+impl<'a, A: Axes> DirtyQueue<'a, A> {
+    /// `lines` holds the number of lines along each axis.
+    pub fn new(passes: &'a [Pass], lines: &[usize]) -> Self {
+        DirtyQueue {
+            dirty: DirtyLines::new(passes, lines),
+            passes,
+            axes: A::all(),
+        }
+    }
+
+    fn next(&mut self) -> Option<Task<'a, A>> {
+        for (p, pass) in self.passes.iter().enumerate() {
+            for (a, axis) in self.axes.iter().enumerate() {
+                if self.dirty.is_dirty(p, a) {
+                    return Some(Task {
+                        pass,
+                        axis: *axis,
+                        lines: self.dirty.take(p, a),
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'a, A: Axes> Schedule<'a, A> for DirtyQueue<'a, A> {
+    fn initial(&mut self) -> Option<Task<'a, A>> {
+        self.next()
+    }
+
+    fn succeeded(&mut self, changes: &[FixedBitSet]) -> Option<Task<'a, A>> {
+        self.dirty.mark(self.passes, changes);
+        self.next()
+    }
+
+    fn failed(&mut self) -> Option<Task<'a, A>> {
+        self.next()
+    }
+}
+
+/// The built-in schedules, as picked with `--strategy`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Strategy {
+    Restart,
+    RoundRobin,
+    Dirty,
+}
+
+impl Strategy {
+    pub fn schedule<'a, B: Board>(
+        &self,
+        passes: &'a [Pass],
+        puzzle: &B,
+    ) -> Box<dyn Schedule<'a, B::Axis> + 'a> {
+        let lines: Vec<usize> = B::Axis::all()
+            .iter()
+            .map(|axis| puzzle.lines(*axis))
+            .collect();
+        match self {
+            Strategy::Restart => Box::new(Restart::new(passes, &lines)),
+            Strategy::RoundRobin => Box::new(RoundRobin::new(passes, &lines)),
+            Strategy::Dirty => Box::new(DirtyQueue::new(passes, &lines)),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "restart" => Ok(Strategy::Restart),
+            "round-robin" => Ok(Strategy::RoundRobin),
+            "dirty" => Ok(Strategy::Dirty),
+            _ => Err("unrecognized strategy"),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::Restart => write!(f, "restart"),
+            Strategy::RoundRobin => write!(f, "round-robin"),
+            Strategy::Dirty => write!(f, "dirty"),
+        }
+    }
+}
+
+/// A task that was run on the puzzle, with what it found.
+pub struct Step<'a, A = Axis> {
+    /// The number of the task, counting from 1.
+    pub round: usize,
+    pub pass: &'a Pass,
+    pub axis: A,
+    /// The lines the pass was run on.
+    pub lines: FixedBitSet,
+    /// The hints found, which have been applied to the puzzle.
+    pub hints: Vec<puzzle::Hint<Hint, A>>,
+    /// The lines changed along each axis, in the order of `Axes::all`.
+    pub changes: Vec<FixedBitSet>,
+    /// Number of cells that were decided by the hints.
+    pub decided: usize,
+    /// Time spent running the pass and applying its hints.
+    pub time: Duration,
+}
+
+/// Runs tasks on a puzzle one at a time, until the schedule runs out of them
+/// or the puzzle is complete.
+///
+/// The puzzle can be looked at between steps with `puzzle`.
+pub struct Steps<'a, 'p, B: Board> {
+    puzzle: &'p mut B,
+    passes: &'a [Pass],
+    schedule: Box<dyn Schedule<'a, B::Axis> + 'a>,
+    next_task: Option<Task<'a, B::Axis>>,
+    cache: Option<&'a LineCache>,
+    jobs: usize,
+    round: usize,
+}
+
+impl<'a, 'p, B: Board> Steps<'a, 'p, B> {
+    /// Runs the tasks of `schedule`, which can be one of the built-in
+    /// strategies made with `Strategy::schedule` or a schedule of its own.
+    pub fn new(
+        puzzle: &'p mut B,
+        passes: &'a [Pass],
+        mut schedule: Box<dyn Schedule<'a, B::Axis> + 'a>,
+        cache: Option<&'a LineCache>,
+        jobs: usize,
+    ) -> Self {
+        let next_task = schedule.initial();
+        Steps {
+            puzzle,
+            passes,
+            schedule,
+            next_task,
+            cache,
+            jobs,
+            round: 0,
+        }
+    }
+
+    pub fn puzzle(&self) -> &B {
+        self.puzzle
+    }
+
+    /// Number of tasks run so far.
+    pub fn rounds(&self) -> usize {
+        self.round
+    }
+}
+
+impl<'a, 'p, B: Board> Iterator for Steps<'a, 'p, B> {
+    type Item = Step<'a, B::Axis>;
+    fn next(&mut self) -> Option<Step<'a, B::Axis>> {
+        let task = self.next_task.take()?;
+        if self.puzzle.is_complete() {
+            return None;
+        }
+        self.round += 1;
+        let decided = self.puzzle.decided();
+        let start = Instant::now();
+        let (hints, changes) = task.apply(self.puzzle, self.passes, self.cache, self.jobs);
+        let time = start.elapsed();
+        self.next_task = if hints.is_empty() {
+            self.schedule.failed()
+        } else {
+            self.schedule.succeeded(&changes)
+        };
+        Some(Step {
+            round: self.round,
+            pass: task.pass,
+            axis: task.axis,
+            lines: task.lines,
+            hints,
+            changes,
+            decided: self.puzzle.decided() - decided,
+            time,
+        })
+    }
+}
+
+/// Gets told about the progress of a solve.
+pub trait Observer<B: Board> {
+    fn started(&mut self, _puzzle: &B) {}
+    /// Called after every task. Returning false stops the solve.
+    fn stepped(&mut self, _step: &Step<B::Axis>, _puzzle: &B) -> bool {
+        true
+    }
+    /// Called once the solve is over, with the number of tasks run.
+    fn finished(&mut self, _puzzle: &B, _rounds: usize) {}
+}
+
+/// Like `run`, telling the observer about each step.
+pub fn observe<'a, B: Board>(
+    puzzle: &mut B,
+    passes: &'a [Pass],
+    schedule: Box<dyn Schedule<'a, B::Axis> + 'a>,
+    cache: Option<&'a LineCache>,
+    jobs: usize,
+    observer: &mut dyn Observer<B>,
+) -> usize {
+    observer.started(puzzle);
+    let mut steps = Steps::new(puzzle, passes, schedule, cache, jobs);
+    while let Some(step) = steps.next() {
+        if !observer.stepped(&step, steps.puzzle()) {
+            break;
+        }
+    }
+    let rounds = steps.rounds();
+    observer.finished(puzzle, rounds);
+    rounds
+}
+
+/// Runs tasks on the puzzle until the schedule runs out of them or the
+/// puzzle is complete, and returns the number of tasks run.
+pub fn run<'a, B: Board>(
+    puzzle: &mut B,
+    passes: &'a [Pass],
+    schedule: Box<dyn Schedule<'a, B::Axis> + 'a>,
+    cache: Option<&'a LineCache>,
+    jobs: usize,
+) -> usize {
+    Steps::new(puzzle, passes, schedule, cache, jobs).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use parser;
+    use puzzle::Puzzle;
+    use triddler::Triddler;
+
+    fn solve(
+        strategy: Strategy,
+        passes: &[Pass],
+        cache: Option<&LineCache>,
+        jobs: usize,
+        s: &str,
+    ) -> (String, usize) {
+        let ast: parser::Puzzle = s.parse().unwrap();
+        let mut puzzle = Puzzle::try_from_ast(ast).unwrap();
+        let schedule = strategy.schedule(passes, &puzzle);
+        let pass_counter = run(&mut puzzle, passes, schedule, cache, jobs);
+        (format!("{}", puzzle.as_ast()), pass_counter)
+    }
+
+    #[test]
+    fn parse_passes() {
+        assert!("crowded".parse::<Pass>().unwrap().is_one_shot());
+        assert!(!"discrete".parse::<Pass>().unwrap().is_one_shot());
+        assert!("bogus".parse::<Pass>().is_err());
+    }
+
+    #[test]
+    fn strategies_agree() {
+        let puzzle = "[1,1,2;1,2;6;1|3;2;1,1;2;3;1,1]";
+        let passes = Pass::all();
+        let (expected, _) = solve(Strategy::Restart, &passes, None, 1, puzzle);
+        for strategy in &[Strategy::RoundRobin, Strategy::Dirty] {
+            let (actual, _) = solve(*strategy, &passes, None, 1, puzzle);
+            assert_eq!(actual, expected, "{}", strategy);
+        }
+    }
+
+    #[test]
+    fn lookahead_strategies_agree() {
+        // lookahead only reaches the same grid every time if its probes are
+        // followed by a pass that solves lines exactly
+        let puzzles = [
+            "[1;1;1|1;2]",
+            "[1,1;2;2,1;1,1|1,1;2;1;1;3]",
+            "[1,1;1,1;1,1,1;1,1;1;1,1,1|1;2;1;2;;1;1,2;1,1,1]",
+            "[1;3;4;1,1;1,2,1;3,1;3|1,2;2,2;1,2;1,3;1,1;1,1;3]",
+        ];
+        for names in &[
+            "crowded,discrete,lookahead",
+            "crowded,complete,edge,gap,ownership,continuous,discrete,lookahead",
+        ] {
+            let passes: Vec<Pass> = names.split(',').map(|name| name.parse().unwrap()).collect();
+            for puzzle in &puzzles {
+                let (expected, _) = solve(Strategy::Restart, &passes, None, 1, puzzle);
+                for strategy in &[Strategy::RoundRobin, Strategy::Dirty] {
+                    let (actual, _) = solve(*strategy, &passes, None, 1, puzzle);
+                    assert_eq!(actual, expected, "{} {} {}", puzzle, names, strategy);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cache_agrees() {
+        let puzzle = "[2;1,1,1;1,1,2;3,1,2,3;1,2,1,1,2;1,1,1,1,3;2,1,1,1;1,1,1,1,1;1,1,1,1;2,1,1,1;6,1;7;10;12;6|6;1,1,3;1,3;1,1,1,4;1,4;1,6;6,3;1,1,3;1,6,1,2;1,2,1,3;1,5,3;1,3;3,2;3,2;3,2]";
+        let passes = Pass::all();
+        let cache = LineCache::new();
+        let expected = solve(Strategy::Restart, &passes, None, 1, puzzle);
+        assert_eq!(
+            solve(Strategy::Restart, &passes, Some(&cache), 1, puzzle),
+            expected
+        );
+        let misses = cache
+            .stats()
+            .iter()
+            .map(|(_, stats)| stats.misses)
+            .sum::<usize>();
+        assert_eq!(
+            solve(Strategy::Restart, &passes, Some(&cache), 1, puzzle),
+            expected
+        );
+        let stats = cache.stats();
+        assert_eq!(
+            stats.iter().map(|(_, stats)| stats.misses).sum::<usize>(),
+            misses
+        );
+        assert!(stats.iter().all(|(_, stats)| stats.hits > 0));
+    }
+
+    #[test]
+    fn parallel_agrees() {
+        let puzzle = "[2;1,1,1;1,1,2;3,1,2,3;1,2,1,1,2;1,1,1,1,3;2,1,1,1;1,1,1,1,1;1,1,1,1;2,1,1,1;6,1;7;10;12;6|6;1,1,3;1,3;1,1,1,4;1,4;1,6;6,3;1,1,3;1,6,1,2;1,2,1,3;1,5,3;1,3;3,2;3,2;3,2]";
+        let passes = Pass::all();
+        let cache = LineCache::new();
+        let expected = solve(Strategy::Restart, &passes, None, 1, puzzle);
+        for jobs in 2..5 {
+            assert_eq!(
+                solve(Strategy::Restart, &passes, None, jobs, puzzle),
+                expected
+            );
+        }
+        assert_eq!(
+            solve(Strategy::Restart, &passes, Some(&cache), 4, puzzle),
+            expected
+        );
+    }
+
+    #[test]
+    fn colored() {
+        let puzzle = "[1r,1b;2r;1b|2r;1r,1b;1b]";
+        let passes = Pass::all();
+        let (expected, _) = solve(Strategy::Restart, &passes, None, 1, puzzle);
+        assert_eq!(expected, "[1r,1b;2r;1b|2r;1r,1b;1b|rrx;xrb;bxx]");
+        let cache = LineCache::new();
+        for strategy in &[Strategy::RoundRobin, Strategy::Dirty] {
+            let (actual, _) = solve(*strategy, &passes, Some(&cache), 2, puzzle);
+            assert_eq!(actual, expected, "{}", strategy);
+        }
+    }
+
+    #[test]
+    fn masked() {
+        let puzzle = "[3;1,1;3|3;1,1;3|...;._.;...]";
+        let (actual, _) = solve(Strategy::Restart, &Pass::all(), None, 1, puzzle);
+        assert_eq!(actual, "[3;1,1;3|3;1,1;3|###;#_#;###]");
+    }
+
+    #[test]
+    fn colored_fallback() {
+        let ast: parser::Puzzle = "[1r;1r;1r;|3r]".parse().unwrap();
+        let mut puzzle = Puzzle::try_from_ast(ast).unwrap();
+        let mut lines = FixedBitSet::with_capacity(1);
+        lines.put(0);
+        for pass in Pass::all() {
+            let hints = pass.run_puzzle(&Axis::Horz, &puzzle, &lines);
+            match pass {
+                Pass::CrowdedClue(_) | Pass::DiscreteRange(_) => assert!(!hints.is_empty()),
+                // these only know about monochrome lines
+                _ => assert!(hints.is_empty(), "{:?}", pass),
+            }
+        }
+        let passes = Pass::all();
+        let schedule = Strategy::Restart.schedule(&passes, &puzzle);
+        run(&mut puzzle, &passes, schedule, None, 1);
+        assert!(puzzle.is_solved());
+        assert!(puzzle.is_finished(Axis::Horz, 0));
+    }
+
+    #[test]
+    fn custom_schedule() {
+        struct Once<'a>(Option<Task<'a>>);
+        impl<'a> Schedule<'a> for Once<'a> {
+            fn initial(&mut self) -> Option<Task<'a>> {
+                self.0.take()
+            }
+            fn succeeded(&mut self, _changes: &[FixedBitSet]) -> Option<Task<'a>> {
+                None
+            }
+            fn failed(&mut self) -> Option<Task<'a>> {
+                None
+            }
+        }
+        let passes = vec!["crowded".parse().unwrap()];
+        let mut puzzle = Puzzle::try_from_ast("[2;2|2;2]".parse().unwrap()).unwrap();
+        let mut lines = FixedBitSet::with_capacity(2);
+        lines.put(1);
+        let task = Task {
+            pass: &passes[0],
+            axis: Axis::Horz,
+            lines,
+        };
+        assert_eq!(
+            run(&mut puzzle, &passes, Box::new(Once(Some(task))), None, 1),
+            1
+        );
+        assert_eq!(format!("{}", puzzle.as_ast()), "[2;2|2;2|..;##]");
+    }
+
+    #[test]
+    fn lookahead() {
+        let puzzle = "[1;3;4;1,1;1,2,1;3,1;3|1,2;2,2;1,2;1,3;1,1;1,1;3]";
+        let mut passes = Pass::all();
+        let (stuck, _) = solve(Strategy::Restart, &passes, None, 1, puzzle);
+        assert!(stuck.contains('.'));
+        passes.push("lookahead".parse().unwrap());
+        let (actual, _) = solve(Strategy::Restart, &passes, None, 1, puzzle);
+        assert_eq!(
+            actual,
+            "[1;3;4;1,1;1,2,1;3,1;3|1,2;2,2;1,2;1,3;1,1;1,1;3|x#x##xx;##xxx##;x#xxx##;xx#x###;xx#x#xx;xx#xx#x;xx###xx]"
+        );
+    }
+
+    #[test]
+    fn steps() {
+        let puzzle = "[1,1,2;1,2;6;1|3;2;1,1;2;3;1,1]";
+        let (expected, rounds) = solve(Strategy::Restart, &Pass::all(), None, 1, puzzle);
+        let mut puzzle = Puzzle::try_from_ast(puzzle.parse().unwrap()).unwrap();
+        let passes = Pass::all();
+        let schedule = Strategy::Restart.schedule(&passes, &puzzle);
+        let steps: Vec<Step> = Steps::new(&mut puzzle, &passes, schedule, None, 1).collect();
+        assert_eq!(steps.len(), rounds);
+        assert_eq!(format!("{}", puzzle.as_ast()), expected);
+        let decided: usize = steps.iter().map(|step| step.decided).sum();
+        assert_eq!(decided, puzzle.decided());
+        for step in steps.iter().filter(|step| step.decided > 0) {
+            assert!(step.changes.iter().any(|lines| lines.count_ones(..) > 0));
+        }
+    }
+
+    #[test]
+    fn observer() {
+        struct Stop {
+            stepped: usize,
+            finished: Option<usize>,
+        }
+        impl<B: Board> Observer<B> for Stop {
+            fn stepped(&mut self, step: &Step<B::Axis>, _puzzle: &B) -> bool {
+                self.stepped += 1;
+                step.round < 3
+            }
+            fn finished(&mut self, _puzzle: &B, rounds: usize) {
+                self.finished = Some(rounds);
+            }
+        }
+        let puzzle = "[1,1,2;1,2;6;1|3;2;1,1;2;3;1,1]";
+        let mut puzzle = Puzzle::try_from_ast(puzzle.parse().unwrap()).unwrap();
+        let mut stop = Stop {
+            stepped: 0,
+            finished: None,
+        };
+        let passes = Pass::all();
+        let schedule = Strategy::Restart.schedule(&passes, &puzzle);
+        let rounds = observe(&mut puzzle, &passes, schedule, None, 1, &mut stop);
+        assert_eq!(rounds, 3);
+        assert_eq!(stop.stepped, 3);
+        assert_eq!(stop.finished, Some(3));
+        assert!(!puzzle.is_complete());
+
+        let triddler = "<1;1,1;2,1|3,1;2;|1,2;2;1>";
+        let mut triddler = Triddler::try_from_ast(triddler.parse().unwrap()).unwrap();
+        let mut stop = Stop {
+            stepped: 0,
+            finished: None,
+        };
+        let schedule = Strategy::Restart.schedule(&passes, &triddler);
+        let rounds = observe(&mut triddler, &passes, schedule, None, 1, &mut stop);
+        assert_eq!(stop.stepped, rounds);
+        assert_eq!(stop.finished, Some(rounds));
+    }
+
+    #[test]
+    fn lookahead_sees_every_line() {
+        let passes = vec!["discrete".parse().unwrap(), "lookahead".parse().unwrap()];
+        let mut schedule = DirtyQueue::<Axis>::new(&passes, &[3, 3]);
+        // run every pass on every line once, finding nothing
+        let mut task = schedule.initial();
+        while task.is_some() {
+            task = schedule.failed();
+        }
+        let mut changes = vec![FixedBitSet::with_capacity(3); 2];
+        changes[0].put(1);
+        changes[1].put(2);
+        let lines = |task: Option<Task>| {
+            let task = task.unwrap();
+            (
+                task.pass.name(),
+                task.axis,
+                task.lines.ones().collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(
+            lines(schedule.succeeded(&changes)),
+            ("discrete", Axis::Horz, vec![1])
+        );
+        assert_eq!(lines(schedule.failed()), ("discrete", Axis::Vert, vec![2]));
+        assert_eq!(
+            lines(schedule.failed()),
+            ("lookahead", Axis::Horz, vec![0, 1, 2])
+        );
+        assert_eq!(
+            lines(schedule.failed()),
+            ("lookahead", Axis::Vert, vec![0, 1, 2])
+        );
+        assert!(schedule.failed().is_none());
+    }
+
+    #[test]
+    fn restart_order() {
+        let passes = Pass::all();
+        let mut schedule = Restart::<Axis>::new(&passes, &[1, 1]);
+        let changes = vec![FixedBitSet::with_capacity(1); 2];
+        let mut order = vec![];
+        let mut task = schedule.initial();
+        for outcome in &[
+            true, true, false, false, false, false, false, false, false, false, true, false, false,
+            false, false, false, false, false, false, false, false, false, false,
+        ] {
+            let t = task.unwrap();
+            order.push(format!("{:?} {:?}", t.pass, t.axis));
+            task = if *outcome {
+                schedule.succeeded(&changes)
+            } else {
+                schedule.failed()
+            };
+        }
+        assert!(task.is_none());
+        assert_eq!(
+            order,
+            vec![
+                "CrowdedClue(CrowdedCluePass) Horz",
+                "CrowdedClue(CrowdedCluePass) Vert",
+                "CompleteLine(CompleteLinePass) Horz",
+                "CompleteLine(CompleteLinePass) Vert",
+                "Edge(EdgePass) Horz",
+                "Edge(EdgePass) Vert",
+                "SmallGap(SmallGapPass) Horz",
+                "SmallGap(SmallGapPass) Vert",
+                "BlockOwnership(BlockOwnershipPass) Horz",
+                "BlockOwnership(BlockOwnershipPass) Vert",
+                "ContinuousRange(ContinuousRangePass) Horz",
+                "CompleteLine(CompleteLinePass) Vert",
+                "CompleteLine(CompleteLinePass) Horz",
+                "Edge(EdgePass) Vert",
+                "Edge(EdgePass) Horz",
+                "SmallGap(SmallGapPass) Vert",
+                "SmallGap(SmallGapPass) Horz",
+                "BlockOwnership(BlockOwnershipPass) Vert",
+                "BlockOwnership(BlockOwnershipPass) Horz",
+                "ContinuousRange(ContinuousRangePass) Vert",
+                "ContinuousRange(ContinuousRangePass) Horz",
+                "DiscreteRange(DiscreteRangePass) Vert",
+                "DiscreteRange(DiscreteRangePass) Horz",
+            ]
+        );
+    }
+
+    #[test]
+    fn restart_after_one_shot() {
+        let passes = vec!["crowded".parse().unwrap(), "discrete".parse().unwrap()];
+        let mut schedule = Restart::<Axis>::new(&passes, &[1, 1]);
+        let changes = vec![FixedBitSet::with_capacity(1); 2];
+        let mut order = vec![];
+        let mut task = schedule.initial();
+        for outcome in &[true, false, false, false] {
+            let t = task.unwrap();
+            order.push(format!("{} {:?}", t.pass.name(), t.axis));
+            task = if *outcome {
+                schedule.succeeded(&changes)
+            } else {
+                schedule.failed()
+            };
+        }
+        assert!(task.is_none());
+        // crowded failing on one axis does not count against discrete
+        assert_eq!(
+            order,
+            vec![
+                "crowded Horz",
+                "crowded Vert",
+                "discrete Horz",
+                "discrete Vert"
+            ]
+        );
+    }
+
+    mod generated {
+        use super::super::*;
+
+        use oracle;
+        use parser;
+        use parser::Cell;
+        use puzzle::Puzzle;
+
+        use proptest::collection::vec;
+        use proptest::prelude::*;
+        use proptest::strategy::Strategy as Generator;
+        use solver::Strategy;
+
+        fn arb_solution() -> impl Generator<Value = Vec<Vec<bool>>> {
+            (1usize..9, 1usize..9)
+                .prop_flat_map(|(width, height)| vec(vec(any::<bool>(), width), height))
+        }
+
+        /// The one-line format of the puzzle a solution is the answer to.
+        fn puzzle_of(solution: &[Vec<bool>]) -> String {
+            let clue_list = |clues: Vec<Vec<usize>>| {
+                parser::ClueList(clues.into_iter().map(parser::Clue::from).collect())
+            };
+            let horz = solution.iter().map(|row| oracle::runs(row)).collect();
+            let vert = (0..solution[0].len())
+                .map(|x| oracle::runs(&solution.iter().map(|row| row[x]).collect::<Vec<_>>()))
+                .collect();
+            format!("[{}|{}]", clue_list(vert), clue_list(horz))
+        }
+
+        fn cells(puzzle: &Puzzle) -> Vec<Vec<Cell>> {
+            puzzle
+                .as_ast()
+                .grid
+                .unwrap()
+                .0
+                .into_iter()
+                .map(|grid_line| grid_line.0)
+                .collect()
+        }
+
+        fn is_unchanged(changes: &puzzle::Changes) -> bool {
+            changes.horz.ones().next().is_none() && changes.vert.ones().next().is_none()
+        }
+
+        proptest! {
+            #[test]
+            fn solution_reproduces_clues(solution in arb_solution()) {
+                let grid = solution
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|filled| if *filled { "#" } else { "x" })
+                            .collect::<String>()
+                    })
+                    .collect::<Vec<_>>()
+                    .join(";");
+                let s = puzzle_of(&solution);
+                let s = format!("{}|{}]", &s[..s.len() - 1], grid);
+                let puzzle = Puzzle::try_from_ast(s.parse().unwrap()).unwrap();
+                prop_assert!(puzzle.is_solved());
+            }
+
+            #[test]
+            fn hints_only_decide_cells(solution in arb_solution()) {
+                let passes = Pass::all();
+                let ast: parser::Puzzle = puzzle_of(&solution).parse().unwrap();
+                let mut puzzle = Puzzle::try_from_ast(ast).unwrap();
+                let mut schedule = Strategy::Restart.schedule(&passes, &puzzle);
+                let mut next_task = schedule.initial();
+                while let Some(task) = next_task {
+                    let before = cells(&puzzle);
+                    let (hints, changes) = task.apply(&mut puzzle, &passes, None, 1);
+                    let after = cells(&puzzle);
+                    for (y, (before, after)) in before.iter().zip(&after).enumerate() {
+                        for (x, (before, after)) in before.iter().zip(after).enumerate() {
+                            let expected = if solution[y][x] { Cell::Filled } else { Cell::Crossed };
+                            prop_assert!(
+                                *before == Cell::Undecided || before == after,
+                                "{:?} {:?} changed {},{} from {:?} to {:?}",
+                                task.pass, task.axis, x, y, before, after
+                            );
+                            prop_assert!(
+                                *after == Cell::Undecided || *after == expected,
+                                "{:?} {:?} decided {},{} as {:?}",
+                                task.pass, task.axis, x, y, after
+                            );
+                        }
+                    }
+                    next_task = if hints.is_empty() {
+                        schedule.failed()
+                    } else {
+                        schedule.succeeded(&changes)
+                    };
+                }
+                if puzzle.is_complete() {
+                    prop_assert!(puzzle.is_solved());
+                }
+            }
+
+            #[test]
+            fn passes_reach_a_fixpoint(solution in arb_solution()) {
+                let passes = Pass::all();
+                let ast: parser::Puzzle = puzzle_of(&solution).parse().unwrap();
+                let mut puzzle = Puzzle::try_from_ast(ast).unwrap();
+                let cells = puzzle.width() * puzzle.height();
+                let mut schedule = Strategy::Restart.schedule(&passes, &puzzle);
+                let mut next_task = schedule.initial();
+                while let Some(task) = next_task {
+                    let (hints, mut changes) = task.apply(&mut puzzle, &passes, None, 1);
+                    for hint in &hints {
+                        hint.apply(&mut puzzle);
+                    }
+                    prop_assert!(is_unchanged(&puzzle.take_changes()));
+                    // every run that finds something decides a cell, so a
+                    // task settles within one run per cell
+                    let mut runs = 0;
+                    loop {
+                        let (again, more) = task.apply(&mut puzzle, &passes, None, 1);
+                        if again.is_empty() {
+                            break;
+                        }
+                        runs += 1;
+                        prop_assert!(runs <= cells, "{:?} {:?}", task.pass, task.axis);
+                        for (changes, more) in changes.iter_mut().zip(&more) {
+                            changes.extend(more.ones());
+                        }
+                    }
+                    next_task = if hints.is_empty() {
+                        schedule.failed()
+                    } else {
+                        schedule.succeeded(&changes)
+                    };
+                }
+                // once the solve is over, no pass finds anything on any line
+                for (axis, len) in &[(Axis::Horz, puzzle.height()), (Axis::Vert, puzzle.width())] {
+                    let mut lines = FixedBitSet::with_capacity(*len);
+                    lines.insert_range(..);
+                    for pass in &passes {
+                        let hints = pass.run_puzzle(axis, &puzzle, &lines);
+                        prop_assert!(hints.is_empty(), "{:?} {:?}", pass, axis);
+                    }
+                }
+            }
+        }
+    }
+}
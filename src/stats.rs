@@ -0,0 +1,163 @@
+use std::fmt;
+use std::time::Duration;
+
+use puzzle::Axes;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PassStats {
+    pub invocations: usize,
+    pub hints: usize,
+    /// Number of cells that went from undecided to filled or crossed.
+    pub cells: usize,
+    pub time: Duration,
+}
+
+impl PassStats {
+    fn add(&mut self, other: &PassStats) {
+        self.invocations += other.invocations;
+        self.hints += other.hints;
+        self.cells += other.cells;
+        self.time += other.time;
+    }
+}
+
+/// Work done by each pass on each axis, by axis name.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    entries: Vec<(&'static str, &'static str, PassStats)>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats { entries: vec![] }
+    }
+
+    /// Statistics per pass name and axis, in the order they were first seen.
+    pub fn entries(&self) -> &[(&'static str, &'static str, PassStats)] {
+        &self.entries
+    }
+
+    pub fn record<A: Axes>(&mut self, pass: &'static str, axis: A, stats: &PassStats) {
+        self.add(pass, axis.name(), stats);
+    }
+
+    fn add(&mut self, pass: &'static str, axis: &'static str, stats: &PassStats) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|(name, a, _)| *name == pass && *a == axis)
+        {
+            Some((_, _, entry)) => entry.add(stats),
+            None => self.entries.push((pass, axis, *stats)),
+        }
+    }
+
+    /// Adds up the statistics of another collection into this one.
+    pub fn merge(&mut self, other: &Stats) {
+        for (pass, axis, stats) in &other.entries {
+            self.add(pass, axis, stats);
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // wide enough for the longest axis name, e.g. "Falling"
+        let axis_width = self
+            .entries
+            .iter()
+            .map(|(_, axis, _)| axis.len())
+            .chain(Some("axis".len()))
+            .max()
+            .unwrap();
+        writeln!(
+            f,
+            "{: <10} {: <axis_width$} {: >8} {: >8} {: >8} {: >12}",
+            "pass",
+            "axis",
+            "runs",
+            "hints",
+            "cells",
+            "time",
+            axis_width = axis_width
+        )?;
+        let mut total = PassStats::default();
+        for (name, axis, stats) in &self.entries {
+            writeln!(
+                f,
+                "{: <10} {: <axis_width$} {: >8} {: >8} {: >8} {: >10.3}ms",
+                name,
+                axis,
+                stats.invocations,
+                stats.hints,
+                stats.cells,
+                stats.time.as_secs_f64() * 1000.0,
+                axis_width = axis_width
+            )?;
+            total.add(stats);
+        }
+        writeln!(
+            f,
+            "{: <label_width$} {: >8} {: >8} {: >8} {: >10.3}ms",
+            "total",
+            total.invocations,
+            total.hints,
+            total.cells,
+            total.time.as_secs_f64() * 1000.0,
+            label_width = 10 + 1 + axis_width
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use puzzle::Axis;
+    use triddler;
+
+    #[test]
+    fn record_and_merge() {
+        let stats = |invocations, hints, cells| PassStats {
+            invocations,
+            hints,
+            cells,
+            time: Duration::from_millis(1),
+        };
+        let mut a = Stats::new();
+        a.record("crowded", Axis::Horz, &stats(1, 2, 3));
+        a.record("crowded", Axis::Vert, &stats(1, 0, 0));
+        a.record("crowded", Axis::Horz, &stats(1, 1, 1));
+        let mut b = Stats::new();
+        b.record("discrete", Axis::Vert, &stats(1, 4, 5));
+        b.merge(&a);
+        assert_eq!(
+            b.entries(),
+            &[
+                ("discrete", "Vert", stats(1, 4, 5)),
+                (
+                    "crowded",
+                    "Horz",
+                    PassStats {
+                        invocations: 2,
+                        hints: 3,
+                        cells: 4,
+                        time: Duration::from_millis(2),
+                    }
+                ),
+                ("crowded", "Vert", stats(1, 0, 0)),
+            ][..]
+        );
+    }
+
+    #[test]
+    fn columns_line_up() {
+        let mut stats = Stats::new();
+        stats.record("crowded", Axis::Horz, &PassStats::default());
+        stats.record("crowded", triddler::Axis::Falling, &PassStats::default());
+        let shown = stats.to_string();
+        let widths: Vec<usize> = shown.lines().map(|line| line.len()).collect();
+        assert!(widths.iter().all(|width| *width == widths[0]), "{}", shown);
+        assert!(shown.contains("crowded    Falling        0"), "{}", shown);
+    }
+}
@@ -0,0 +1,491 @@
+//! Triddlers: nonograms on a triangle of triangular cells.
+//!
+//! A triddler of side `n` has `n` rows, where row `y` holds `2y + 1` cells
+//! and the cells at even positions point up. Lines run in three directions:
+//! along the rows, rising parallel to the left edge and falling parallel to
+//! the right edge. Every line is read from left to right, so rising lines
+//! start at the bottom edge and falling lines start at the left edge.
+//!
+//! The cells are kept in a `Grid` of `2n - 1` by `n` cells, with each row
+//! centered under the tip of the triangle.
+
+use fixedbitset::FixedBitSet;
+use std::borrow::Borrow;
+use std::borrow::Cow;
+use std::fmt;
+use std::mem;
+
+use parser;
+use parser::Cell;
+use parser::ClueList;
+use parser::GridLine;
+use puzzle::Axes;
+use puzzle::Board;
+use puzzle::Grid;
+use puzzle::Line;
+use puzzle::LineHint;
+use puzzle::LineMut;
+use puzzle::Theme;
+
+/// A direction of lines through a triddler.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Axis {
+    Horz,
+    Rising,
+    Falling,
+}
+
+static AXES: [Axis; 3] = [Axis::Horz, Axis::Rising, Axis::Falling];
+
+impl Axes for Axis {
+    fn all() -> &'static [Axis] {
+        &AXES
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Axis::Horz => "Horz",
+            Axis::Rising => "Rising",
+            Axis::Falling => "Falling",
+        }
+    }
+}
+
+/// The row and grid column of cell `i` of a line.
+fn coords(side: usize, axis: Axis, line: usize, i: usize) -> (usize, usize) {
+    let (y, pos) = match axis {
+        Axis::Horz => (line, i),
+        Axis::Rising => (side - 1 - i / 2, 2 * line + i % 2),
+        Axis::Falling => (line + i.div_ceil(2), i),
+    };
+    (pos + side - 1 - y, y)
+}
+
+/// The lines through a cell along each axis, in the order of `AXES`.
+fn lines_through(side: usize, x: usize, y: usize) -> [usize; 3] {
+    let pos = x + y + 1 - side;
+    [y, pos / 2, y - pos.div_ceil(2)]
+}
+
+fn line_len(side: usize, axis: Axis, line: usize) -> usize {
+    match axis {
+        Axis::Horz => 2 * line + 1,
+        _ => 2 * (side - 1 - line) + 1,
+    }
+}
+
+pub struct TriLine<'a> {
+    grid: &'a Grid,
+    side: usize,
+    axis: Axis,
+    line: usize,
+}
+
+impl<'a> Line for TriLine<'a> {
+    fn len(&self) -> usize {
+        line_len(self.side, self.axis, self.line)
+    }
+    fn get(&self, i: usize) -> Cell {
+        let (x, y) = coords(self.side, self.axis, self.line, i);
+        self.grid.get(x, y)
+    }
+    fn is_crossed(&self, i: usize) -> bool {
+        let (x, y) = coords(self.side, self.axis, self.line, i);
+        self.grid.is_crossed(x, y)
+    }
+    fn is_filled(&self, i: usize) -> bool {
+        let (x, y) = coords(self.side, self.axis, self.line, i);
+        self.grid.is_filled(x, y)
+    }
+}
+
+pub struct TriLineMut<'a> {
+    grid: &'a mut Grid,
+    side: usize,
+    axis: Axis,
+    line: usize,
+}
+
+impl<'a> Line for TriLineMut<'a> {
+    fn len(&self) -> usize {
+        line_len(self.side, self.axis, self.line)
+    }
+    fn get(&self, i: usize) -> Cell {
+        let (x, y) = coords(self.side, self.axis, self.line, i);
+        self.grid.get(x, y)
+    }
+    fn is_crossed(&self, i: usize) -> bool {
+        let (x, y) = coords(self.side, self.axis, self.line, i);
+        self.grid.is_crossed(x, y)
+    }
+    fn is_filled(&self, i: usize) -> bool {
+        let (x, y) = coords(self.side, self.axis, self.line, i);
+        self.grid.is_filled(x, y)
+    }
+}
+
+impl<'a> LineMut for TriLineMut<'a> {
+    fn cross(&mut self, i: usize) -> bool {
+        let (x, y) = coords(self.side, self.axis, self.line, i);
+        self.grid.cross(x, y)
+    }
+    fn fill(&mut self, i: usize) -> bool {
+        let (x, y) = coords(self.side, self.axis, self.line, i);
+        self.grid.fill(x, y)
+    }
+    fn exclude(&mut self, i: usize, color: usize) -> bool {
+        let (x, y) = coords(self.side, self.axis, self.line, i);
+        self.grid.exclude(x, y, color)
+    }
+}
+
+pub struct Triddler<'a> {
+    horz_clues: Cow<'a, ClueList>,
+    rising_clues: Cow<'a, ClueList>,
+    falling_clues: Cow<'a, ClueList>,
+    grid: Grid,
+    /// The lines along each axis with cells changed since the last
+    /// `take_changed_lines`.
+    changed: Vec<FixedBitSet>,
+}
+
+impl<'a> Triddler<'a> {
+    /// Number of rows, and of lines along each axis.
+    pub fn side(&self) -> usize {
+        self.horz_clues.0.len()
+    }
+
+    fn clues(&self, axis: Axis) -> &ClueList {
+        match axis {
+            Axis::Horz => &self.horz_clues,
+            Axis::Rising => &self.rising_clues,
+            Axis::Falling => &self.falling_clues,
+        }
+    }
+
+    pub fn line(&self, axis: Axis, line: usize) -> TriLine<'_> {
+        TriLine {
+            grid: &self.grid,
+            side: self.side(),
+            axis,
+            line,
+        }
+    }
+
+    fn line_mut(&mut self, axis: Axis, line: usize) -> TriLineMut<'_> {
+        TriLineMut {
+            side: self.side(),
+            grid: &mut self.grid,
+            axis,
+            line,
+        }
+    }
+
+    fn cells<'b>(&'b self) -> impl Iterator<Item = Cell> + 'b {
+        (0..self.side()).flat_map(move |y| {
+            let line = self.line(Axis::Horz, y);
+            (0..line.len()).map(move |i| line.get(i))
+        })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.cells().all(|cell| cell != Cell::Undecided)
+    }
+
+    /// Number of cells that are filled or crossed.
+    pub fn decided(&self) -> usize {
+        self.cells().filter(|cell| *cell != Cell::Undecided).count()
+    }
+
+    pub fn is_contradictory(&self) -> bool {
+        self.cells().any(|cell| cell == Cell::Impossible)
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.is_complete()
+            && !self.is_contradictory()
+            && AXES.iter().all(|axis| {
+                self.clues(*axis)
+                    .0
+                    .iter()
+                    .enumerate()
+                    .all(|(i, clue)| self.line(*axis, i).filled_runs() == clue.numbers)
+            })
+    }
+
+    pub fn try_from_ast(ast: parser::Triddler<'a>) -> Result<Triddler<'a>, String> {
+        let side = ast.horz_clues.0.len();
+        for (name, clues) in &[
+            ("rising", &ast.rising_clues),
+            ("falling", &ast.falling_clues),
+        ] {
+            if clues.0.len() != side {
+                return Err(format!(
+                    "number of {} clues not same as number of rows ({} vs {})",
+                    name,
+                    clues.0.len(),
+                    side
+                ));
+            }
+        }
+        for (axis, clues) in
+            AXES.iter()
+                .zip(&[&ast.horz_clues, &ast.rising_clues, &ast.falling_clues])
+        {
+            for (i, clue) in clues.0.iter().enumerate() {
+                if clue.is_colored() {
+                    return Err(format!("colored clue {} in a triddler", clue));
+                }
+                if clue.has_zero() {
+                    return Err(format!("{:?} clue {} ({}) has a zero", axis, i + 1, clue));
+                }
+                let len = line_len(side, *axis, i);
+                let needed = clue
+                    .numbers
+                    .iter()
+                    .try_fold(clue.numbers.len().saturating_sub(1), |sum, n| {
+                        sum.checked_add(*n)
+                    });
+                if needed.is_none_or(|needed| needed > len) {
+                    return Err(format!(
+                        "{:?} clue {} ({}) does not fit in {} cells",
+                        axis,
+                        i + 1,
+                        clue,
+                        len
+                    ));
+                }
+            }
+        }
+        let mut grid = Grid::new((2 * side).max(1) - 1, side);
+        if let Some(cells) = ast.grid {
+            if cells.0.len() != side {
+                return Err(format!(
+                    "number of rows not same as number of grid lines ({} vs {})",
+                    side,
+                    cells.0.len()
+                ));
+            }
+            for (y, grid_line) in cells.0.iter().enumerate() {
+                if grid_line.0.len() != 2 * y + 1 {
+                    return Err(format!(
+                        "grid line {} has {} cells instead of {}",
+                        y + 1,
+                        grid_line.0.len(),
+                        2 * y + 1
+                    ));
+                }
+                for (i, cell) in grid_line.0.iter().enumerate() {
+                    let (x, y) = coords(side, Axis::Horz, y, i);
+                    match cell {
+                        Cell::Filled => {
+                            grid.fill(x, y);
+                        }
+                        Cell::Crossed => {
+                            grid.cross(x, y);
+                        }
+                        Cell::Impossible => {
+                            grid.fill(x, y);
+                            grid.cross(x, y);
+                        }
+                        Cell::Masked => grid.mask(x, y),
+                        Cell::Undecided => {}
+                        Cell::Colored(_) | Cell::Unresolved => {
+                            return Err(format!("colored cell {} in a triddler", cell));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Triddler {
+            horz_clues: ast.horz_clues,
+            rising_clues: ast.rising_clues,
+            falling_clues: ast.falling_clues,
+            grid,
+            changed: vec![FixedBitSet::with_capacity(side); AXES.len()],
+        })
+    }
+
+    pub fn as_ast(&self) -> parser::Triddler<'_> {
+        let grid_lines = (0..self.side())
+            .map(|y| {
+                let line = self.line(Axis::Horz, y);
+                GridLine((0..line.len()).map(|i| line.get(i)).collect())
+            })
+            .collect();
+        parser::Triddler {
+            horz_clues: Cow::Borrowed(self.horz_clues.borrow()),
+            rising_clues: Cow::Borrowed(self.rising_clues.borrow()),
+            falling_clues: Cow::Borrowed(self.falling_clues.borrow()),
+            grid: Some(parser::Grid(grid_lines)),
+        }
+    }
+}
+
+impl<'a> Board for Triddler<'a> {
+    type Axis = Axis;
+    fn lines(&self, _axis: Axis) -> usize {
+        self.side()
+    }
+    fn clue(&self, axis: Axis, line: usize) -> (&[usize], &[usize]) {
+        (&self.clues(axis).0[line].numbers, &[])
+    }
+    fn is_colored(&self) -> bool {
+        false
+    }
+    fn with_line<R, F: FnOnce(&dyn Line) -> R>(&self, axis: Axis, line: usize, f: F) -> R {
+        f(&self.line(axis, line))
+    }
+    fn apply(&mut self, axis: Axis, line: usize, hint: &dyn LineHint) {
+        let before: Vec<Cell> = {
+            let line = self.line(axis, line);
+            (0..line.len()).map(|i| line.get(i)).collect()
+        };
+        hint.apply(&mut self.line_mut(axis, line));
+        let side = self.side();
+        for (i, cell) in before.into_iter().enumerate() {
+            let (x, y) = coords(side, axis, line, i);
+            if self.grid.get(x, y) != cell {
+                for (changed, line) in self.changed.iter_mut().zip(&lines_through(side, x, y)) {
+                    changed.put(*line);
+                }
+            }
+        }
+    }
+    fn take_changed_lines(&mut self) -> Vec<FixedBitSet> {
+        let side = self.side();
+        mem::replace(
+            &mut self.changed,
+            vec![FixedBitSet::with_capacity(side); AXES.len()],
+        )
+    }
+    fn is_complete(&self) -> bool {
+        Triddler::is_complete(self)
+    }
+    fn decided(&self) -> usize {
+        Triddler::decided(self)
+    }
+}
+
+/// Shows a triddler in a theme, as a triangle of cells with the row clues to
+/// its left and the diagonal clues below it.
+pub struct View<'a> {
+    triddler: &'a Triddler<'a>,
+    theme: &'a Theme,
+}
+
+impl<'a> View<'a> {
+    pub fn new(triddler: &'a Triddler<'a>, theme: &'a Theme) -> Self {
+        View { triddler, theme }
+    }
+
+    fn cell(&self, cell: Cell, up: bool) -> char {
+        match (self.theme, cell, up) {
+            (Theme::Unicode, Cell::Filled, true) => '▲',
+            (Theme::Unicode, Cell::Filled, false) => '▼',
+            (Theme::Unicode, Cell::Undecided, true) => '△',
+            (Theme::Unicode, Cell::Undecided, false) => '▽',
+            (_, Cell::Masked, _) => ' ',
+            (_, Cell::Filled, _) => self.theme.filled(),
+            (_, Cell::Crossed, _) => self.theme.crossed(),
+            (_, Cell::Undecided, _) => self.theme.undecided(),
+            _ => self.theme.impossible(),
+        }
+    }
+}
+
+impl<'a> fmt::Display for View<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self.theme == Theme::Brief {
+            return self.triddler.as_ast().fmt(f);
+        }
+
+        let side = self.triddler.side();
+        let clues: Vec<String> = self
+            .triddler
+            .horz_clues
+            .0
+            .iter()
+            .map(|clue| clue.to_string())
+            .collect();
+        let clue_width = clues.iter().map(String::len).max().unwrap_or(0);
+        for (y, clue) in clues.iter().enumerate() {
+            write!(f, "{: >width$} ", clue, width = clue_width)?;
+            write!(f, "{: >width$}", "", width = side - 1 - y)?;
+            let line = self.triddler.line(Axis::Horz, y);
+            for i in 0..line.len() {
+                write!(f, "{}", self.cell(line.get(i), i % 2 == 0))?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "rising  {}", self.triddler.rising_clues)?;
+        writeln!(f, "falling {}", self.triddler.falling_clues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cache::LineCache;
+    use solver;
+    use solver::Pass;
+    use solver::Strategy;
+
+    fn triddler(s: &str) -> Result<Triddler<'static>, String> {
+        Triddler::try_from_ast(s.parse()?)
+    }
+
+    #[test]
+    fn lines() {
+        // side 3: every cell lies on exactly one line along each axis
+        let t = triddler("<;;|;;|;;>").unwrap();
+        for axis in &AXES {
+            let mut seen = vec![];
+            for line in 0..3 {
+                let len = t.line(*axis, line).len();
+                for i in 0..len {
+                    let (x, y) = coords(3, *axis, line, i);
+                    assert_eq!(lines_through(3, x, y)[axis.index()], line);
+                    seen.push((x, y));
+                }
+            }
+            seen.sort();
+            seen.dedup();
+            assert_eq!(seen.len(), 9, "{:?}", axis);
+        }
+        assert_eq!(coords(3, Axis::Rising, 0, 0), (0, 2));
+        assert_eq!(coords(3, Axis::Rising, 0, 4), (2, 0));
+        assert_eq!(coords(3, Axis::Falling, 0, 0), (2, 0));
+        assert_eq!(coords(3, Axis::Falling, 0, 4), (4, 2));
+    }
+
+    #[test]
+    fn checks() {
+        assert!(triddler("<;|;|;>").is_ok());
+        assert!(triddler("<;|;|>").is_err());
+        assert!(triddler("<;4|;|;>").is_err());
+        assert!(triddler("<;|;3|;>").is_err());
+        assert!(triddler("<;|;|;|#;###>").is_ok());
+        assert!(triddler("<;|;|;|#;##>").is_err());
+        assert!(triddler("<1r;|;|;>").is_err());
+        assert!(triddler("<0;|;|;>").is_err());
+    }
+
+    #[test]
+    fn solves() {
+        let passes = Pass::all();
+        let cache = LineCache::new();
+        for strategy in &[Strategy::Restart, Strategy::RoundRobin, Strategy::Dirty] {
+            for (cache, jobs) in &[(None, 1), (Some(&cache), 2)] {
+                let mut t = triddler("<1;1,1;2,1|3,1;2;|1,2;2;1>").unwrap();
+                let schedule = strategy.schedule(&passes, &t);
+                solver::run(&mut t, &passes, schedule, *cache, *jobs);
+                assert!(t.is_solved(), "{}", strategy);
+                assert_eq!(
+                    t.as_ast().to_string(),
+                    "<1;1,1;2,1|3,1;2;|1,2;2;1|#;#x#;##x#x>"
+                );
+            }
+        }
+    }
+}
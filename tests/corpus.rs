@@ -0,0 +1,136 @@
+//! Solves every puzzle in `tests/corpus.txt` and compares the final states
+//! and pass counts to the expected ones recorded there.
+//!
+//! Run with `NONO_BLESS=1` to record the current results as the expected
+//! ones instead.
+
+extern crate nono;
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use nono::parser;
+use nono::puzzle::Puzzle;
+use nono::solver;
+use nono::solver::Pass;
+use nono::solver::Strategy;
+
+const HEADER: &str = "\
+# Regression corpus for tests/corpus.rs.
+#
+# Each entry is a puzzle, its final state and the number of passes run, as
+# solved with the default passes and strategy. Entries are separated by
+# blank lines.
+";
+
+struct Entry {
+    puzzle: String,
+    expected: String,
+    passes: usize,
+}
+
+fn corpus_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("corpus.txt")
+}
+
+fn read_corpus(s: &str) -> Vec<Entry> {
+    let lines: Vec<&str> = s.lines().filter(|line| !line.starts_with('#')).collect();
+    lines
+        .split(|line| line.trim().is_empty())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry {
+            [puzzle, expected, passes] => Entry {
+                puzzle: puzzle.to_string(),
+                expected: expected.to_string(),
+                passes: passes
+                    .parse()
+                    .unwrap_or_else(|e| panic!("bad pass count {:?}: {}", passes, e)),
+            },
+            _ => panic!("malformed corpus entry: {:?}", entry),
+        })
+        .collect()
+}
+
+fn write_corpus(entries: &[Entry]) -> String {
+    let mut s = HEADER.to_string();
+    for entry in entries {
+        write!(
+            s,
+            "\n{}\n{}\n{}\n",
+            entry.puzzle, entry.expected, entry.passes
+        )
+        .unwrap();
+    }
+    s
+}
+
+fn solve(s: &str) -> (String, usize) {
+    let ast: parser::Puzzle = s.parse().unwrap_or_else(|e| panic!("{}", e));
+    let mut puzzle = Puzzle::try_from_ast(ast).unwrap_or_else(|e| panic!("{}", e));
+    let passes = Pass::all();
+    let schedule = Strategy::Restart.schedule(&passes, &puzzle);
+    let passes = solver::run(&mut puzzle, &passes, schedule, None, 1);
+    (format!("{}", puzzle.as_ast()), passes)
+}
+
+fn grid_lines(s: &str) -> Vec<String> {
+    let ast: parser::Puzzle = s.parse().unwrap_or_else(|e| panic!("{}", e));
+    ast.grid
+        .map(|grid| grid.0.iter().map(|line| line.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Lists the grid lines that differ, expected state first.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected = grid_lines(expected);
+    let actual = grid_lines(actual);
+    let mut s = String::new();
+    for y in 0..expected.len().max(actual.len()) {
+        let e = expected.get(y).map(String::as_str).unwrap_or("");
+        let a = actual.get(y).map(String::as_str).unwrap_or("");
+        if e != a {
+            writeln!(s, "  line {:>3}: {} -> {}", y + 1, e, a).unwrap();
+        }
+    }
+    s
+}
+
+#[test]
+fn corpus() {
+    let path = corpus_path();
+    let contents = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}", e));
+    let mut entries = read_corpus(&contents);
+    assert!(!entries.is_empty(), "empty corpus");
+
+    if env::var_os("NONO_BLESS").is_some() {
+        for entry in &mut entries {
+            let (actual, passes) = solve(&entry.puzzle);
+            entry.expected = actual;
+            entry.passes = passes;
+        }
+        fs::write(&path, write_corpus(&entries)).unwrap();
+        return;
+    }
+
+    let mut report = String::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let (actual, passes) = solve(&entry.puzzle);
+        if actual != entry.expected || passes != entry.passes {
+            writeln!(report, "entry {}: {}", i + 1, entry.puzzle).unwrap();
+            if passes != entry.passes {
+                writeln!(report, "  passes: {} -> {}", entry.passes, passes).unwrap();
+            }
+            report.push_str(&diff(&entry.expected, &actual));
+        }
+    }
+    assert!(
+        report.is_empty(),
+        "solver results differ from {}:\n{}",
+        path.display(),
+        report
+    );
+}